@@ -0,0 +1,275 @@
+// renders the DamagedHelmet like the viewer does and reports the average frame time,
+// then compares stepping the edge functions against the float barycentric test at every pixel
+// the rasterizer started with
+// and interpolating the varyings of a block pixel by pixel against a Vec4 per varying
+// run with `cargo bench`
+use rusterizer::*;
+use std::path::Path;
+use std::time::Instant;
+
+const FRAMES: usize = 60;
+// how often the coverage of the same triangles is walked by each method
+const COVERAGE_RUNS: usize = 20;
+// how many blocks of varyings are interpolated per triangle by each method
+const INTERPOLATION_BLOCKS: usize = 64;
+
+fn bench_helmet(width: usize, height: usize, mesh: &Mesh, texture: &Texture) {
+    let mut framebuffer = Framebuffer::new(width, height);
+    let camera = Camera {
+        aspect_ratio: width as f32 / height as f32,
+        transform: Transform::from_translation(glam::vec3(0.0, 0.0, 3.0)),
+        frustum_near: 0.5,
+        frustum_far: 100.0,
+        ..Default::default()
+    };
+
+    let mut stats = RasterStats::default();
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        framebuffer.clear(0);
+        let rot = frame as f32 / FRAMES as f32 * std::f32::consts::TAU;
+        let model = Transform::from_rotation(glam::Quat::from_euler(
+            glam::EulerRot::XYZ,
+            std::f32::consts::FRAC_PI_2,
+            rot,
+            0.0,
+        ))
+        .local();
+        let uniforms = DefaultUniforms::new(
+            camera.projection() * camera.view() * model,
+            model,
+            Some(texture),
+        );
+        stats += raster_mesh(
+            mesh,
+            &DefaultShader,
+            &DefaultShader,
+            &uniforms,
+            &PipelineState::default(),
+            &RenderSettings::default(),
+            &mut framebuffer,
+        );
+    }
+    let frame_time = start.elapsed().as_secs_f64() / FRAMES as f64;
+    println!(
+        "helmet {}x{}: {:.2} ms/frame ({:.1} fps) on {} tile workers",
+        width,
+        height,
+        frame_time * 1000.0,
+        1.0 / frame_time,
+        tiles::worker_count()
+    );
+    println!(
+        "  hi-z culled {:.1}% of {} binned triangles per frame",
+        stats.culled() as f64 / stats.triangles as f64 * 100.0,
+        stats.triangles / FRAMES as u64
+    );
+}
+
+// the helmet triangles of one frame, as the rasterizer sets them up
+fn helmet_triangles(
+    width: usize,
+    height: usize,
+    mesh: &Mesh,
+) -> Vec<ScreenTriangle<DefaultVaryings>> {
+    let camera = Camera {
+        aspect_ratio: width as f32 / height as f32,
+        transform: Transform::from_translation(glam::vec3(0.0, 0.0, 3.0)),
+        frustum_near: 0.5,
+        frustum_far: 100.0,
+        ..Default::default()
+    };
+    let model =
+        Transform::from_rotation(glam::Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)).local();
+    let uniforms = DefaultUniforms::new(camera.projection() * camera.view() * model, model, None);
+    setup_mesh(
+        mesh,
+        &DefaultShader,
+        &uniforms,
+        &PipelineState::default(),
+        &RenderSettings::default(),
+        glam::vec2(width as f32, height as f32),
+    )
+}
+
+// the covered pixels of every triangle the way the rasterizer used to find them,
+// with float barycentric coordinates computed from scratch at each pixel of its bounding box
+fn coverage_float<V>(triangles: &[ScreenTriangle<V>]) -> u64 {
+    let mut covered = 0;
+    for triangle in triangles {
+        let [sc0, sc1, sc2] = triangle.sc;
+        let area = edge_function(sc0, sc1, sc2);
+        let bb = &triangle.bb;
+        for y in bb.top as usize..=bb.bottom as usize {
+            for x in bb.left as usize..=bb.right as usize {
+                let coords = glam::vec2(x as f32, y as f32) + 0.5;
+                covered += barycentric_coords(coords, sc0, sc1, sc2, area).is_some() as u64;
+            }
+        }
+    }
+    covered
+}
+
+// the same with the fixed point edge functions, evaluated at each pixel
+fn coverage_per_pixel<V>(triangles: &[ScreenTriangle<V>]) -> u64 {
+    let mut covered = 0;
+    for triangle in triangles {
+        let bb = &triangle.bb;
+        for y in bb.top as usize..=bb.bottom as usize {
+            for x in bb.left as usize..=bb.right as usize {
+                let e = triangle.edges.eval(raster::pixel_center_fixed(x, y));
+                covered += triangle.edges.covers(e) as u64;
+            }
+        }
+    }
+    covered
+}
+
+// the same, with the edges evaluated once per triangle and stepped, one span per row
+fn coverage_stepped<V>(triangles: &[ScreenTriangle<V>]) -> u64 {
+    let mut covered = 0;
+    for triangle in triangles {
+        let bb = &triangle.bb;
+        let (left, top) = (bb.left as usize, bb.top as usize);
+        let width = bb.right as usize - left + 1;
+        let step_y = triangle.edges.step_y();
+        let mut e_row = triangle.edges.eval(raster::pixel_center_fixed(left, top));
+        for _ in top..=bb.bottom as usize {
+            if let Some((first, last)) = triangle.edges.row_span(e_row, width) {
+                covered += (last - first + 1) as u64;
+            }
+            e_row = [0, 1, 2].map(|i| e_row[i] + step_y[i]);
+        }
+    }
+    covered
+}
+
+fn bench_edge_stepping(width: usize, height: usize, mesh: &Mesh) {
+    let triangles = helmet_triangles(width, height, mesh);
+    let time = |coverage: &dyn Fn(&[ScreenTriangle<DefaultVaryings>]) -> u64| {
+        let start = Instant::now();
+        let mut covered = 0;
+        for _ in 0..COVERAGE_RUNS {
+            covered = std::hint::black_box(coverage(std::hint::black_box(&triangles)));
+        }
+        (
+            start.elapsed().as_secs_f64() / COVERAGE_RUNS as f64,
+            covered,
+        )
+    };
+    let (float, float_covered) = time(&coverage_float);
+    let (per_pixel, covered) = time(&coverage_per_pixel);
+    let (stepped, stepped_covered) = time(&coverage_stepped);
+    assert_eq!(
+        covered, stepped_covered,
+        "stepping has to cover the same pixels as evaluating the edges at each one"
+    );
+    // the float test has no fill rule, so pixels on shared edges can make its count differ a little
+    println!(
+        "edge functions {}x{}: float barycentrics {:.2} ms ({} pixels), \
+         fixed point per pixel {:.2} ms, stepped {:.2} ms ({} pixels), {:.1}x faster than float",
+        width,
+        height,
+        float * 1000.0,
+        float_covered,
+        per_pixel * 1000.0,
+        stepped * 1000.0,
+        covered,
+        float / stepped
+    );
+}
+
+// the weights of the three vertices for a few blocks, all four lanes different
+fn lane_weights() -> Vec<[glam::Vec4; 3]> {
+    (0..INTERPOLATION_BLOCKS)
+        .map(|i| {
+            let w0 =
+                glam::Vec4::splat(i as f32 / INTERPOLATION_BLOCKS as f32) + LANE_OFFSETS * 0.01;
+            let w1 = (1.0 - w0) * 0.5;
+            [w0, w1, 1.0 - w0 - w1]
+        })
+        .collect()
+}
+
+// what `DefaultShader::fragment_block` gets out of the varyings: a Vec4 of each float
+type ShadedLanes = [glam::Vec4; 8];
+
+// the struct of every pixel first, then the floats gathered into Vec4s
+fn interpolate_per_pixel(pv: &[DefaultVaryings; 3], weights: [glam::Vec4; 3]) -> ShadedLanes {
+    let block = VaryingBlock::new(pv, weights);
+    let varyings: [DefaultVaryings; LANES] = std::array::from_fn(|k| block.lane(k));
+    let lanes = |f: &dyn Fn(&DefaultVaryings) -> f32| {
+        glam::Vec4::from_array(std::array::from_fn::<f32, LANES, _>(|k| f(&varyings[k])))
+    };
+    [
+        lanes(&|v| v.normal.x),
+        lanes(&|v| v.normal.y),
+        lanes(&|v| v.normal.z),
+        lanes(&|v| v.color.x),
+        lanes(&|v| v.color.y),
+        lanes(&|v| v.color.z),
+        lanes(&|v| v.uv.x),
+        lanes(&|v| v.uv.y),
+    ]
+}
+
+// each float of the whole block at once
+fn interpolate_lanes(pv: &[DefaultVaryings; 3], weights: [glam::Vec4; 3]) -> ShadedLanes {
+    let block = VaryingBlock::new(pv, weights);
+    [
+        block.lanes(|v| v.normal.x),
+        block.lanes(|v| v.normal.y),
+        block.lanes(|v| v.normal.z),
+        block.lanes(|v| v.color.x),
+        block.lanes(|v| v.color.y),
+        block.lanes(|v| v.color.z),
+        block.lanes(|v| v.uv.x),
+        block.lanes(|v| v.uv.y),
+    ]
+}
+
+fn bench_interpolation(width: usize, height: usize, mesh: &Mesh) {
+    let triangles = helmet_triangles(width, height, mesh);
+    let weights = lane_weights();
+    let time = |interpolate: &dyn Fn(&[DefaultVaryings; 3], [glam::Vec4; 3]) -> ShadedLanes| {
+        let start = Instant::now();
+        let mut sum = glam::Vec4::ZERO;
+        for _ in 0..COVERAGE_RUNS {
+            sum = glam::Vec4::ZERO;
+            for triangle in std::hint::black_box(&triangles) {
+                for &w in &weights {
+                    sum += std::hint::black_box(interpolate(&triangle.pv, w))
+                        .into_iter()
+                        .sum::<glam::Vec4>();
+                }
+            }
+        }
+        (start.elapsed().as_secs_f64() / COVERAGE_RUNS as f64, sum)
+    };
+    let (per_pixel, sum) = time(&interpolate_per_pixel);
+    let (lanes, lanes_sum) = time(&interpolate_lanes);
+    assert_eq!(sum, lanes_sum, "both have to give the same varyings");
+    println!(
+        "varyings {}x{}: per pixel {:.2} ms, per lane vector {:.2} ms ({:.1}x), {} blocks",
+        width,
+        height,
+        per_pixel * 1000.0,
+        lanes * 1000.0,
+        per_pixel / lanes,
+        triangles.len() * INTERPOLATION_BLOCKS
+    );
+}
+
+fn main() {
+    let mesh = load_gltf(Path::new(
+        "assets/gltf_models/damaged_helmet/DamagedHelmet.gltf",
+    ));
+    let texture = Texture::load(Path::new(
+        "assets/gltf_models/damaged_helmet/Default_albedo.jpg",
+    ));
+    bench_helmet(640, 360, &mesh, &texture);
+    bench_helmet(1280, 720, &mesh, &texture);
+    bench_edge_stepping(640, 360, &mesh);
+    bench_edge_stepping(1280, 720, &mesh);
+    bench_interpolation(640, 360, &mesh);
+}
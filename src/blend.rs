@@ -0,0 +1,187 @@
+use crate::{argb_to_vec4, vec4_to_argb};
+use glam::Vec4;
+
+// what the fragment color (src) and the color already in the framebuffer (dst) are multiplied with
+// `Src` and `Dst` are per channel, the alpha ones scale every channel by the same amount
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    Src,
+    OneMinusSrc,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    Dst,
+    OneMinusDst,
+    DstAlpha,
+    OneMinusDstAlpha,
+}
+
+impl BlendFactor {
+    fn value(self, src: Vec4, dst: Vec4) -> Vec4 {
+        match self {
+            BlendFactor::Zero => Vec4::ZERO,
+            BlendFactor::One => Vec4::ONE,
+            BlendFactor::Src => src,
+            BlendFactor::OneMinusSrc => Vec4::ONE - src,
+            BlendFactor::SrcAlpha => Vec4::splat(src.w),
+            BlendFactor::OneMinusSrcAlpha => Vec4::splat(1.0 - src.w),
+            BlendFactor::Dst => dst,
+            BlendFactor::OneMinusDst => Vec4::ONE - dst,
+            BlendFactor::DstAlpha => Vec4::splat(dst.w),
+            BlendFactor::OneMinusDstAlpha => Vec4::splat(1.0 - dst.w),
+        }
+    }
+}
+
+// how the two scaled colors are combined, `Min` and `Max` ignore the factors
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlendOperation {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlendComponent {
+    pub src_factor: BlendFactor,
+    pub dst_factor: BlendFactor,
+    pub operation: BlendOperation,
+}
+
+impl BlendComponent {
+    pub const REPLACE: Self = Self::new(BlendFactor::One, BlendFactor::Zero);
+    // src over dst, for colors that are already multiplied with their alpha
+    pub const OVER: Self = Self::new(BlendFactor::One, BlendFactor::OneMinusSrcAlpha);
+
+    pub const fn new(src_factor: BlendFactor, dst_factor: BlendFactor) -> Self {
+        Self {
+            src_factor,
+            dst_factor,
+            operation: BlendOperation::Add,
+        }
+    }
+
+    fn apply(&self, src: Vec4, dst: Vec4) -> Vec4 {
+        let s = src * self.src_factor.value(src, dst);
+        let d = dst * self.dst_factor.value(src, dst);
+        match self.operation {
+            BlendOperation::Add => s + d,
+            BlendOperation::Subtract => s - d,
+            BlendOperation::ReverseSubtract => d - s,
+            BlendOperation::Min => src.min(dst),
+            BlendOperation::Max => src.max(dst),
+        }
+    }
+}
+
+// how a fragment is combined with the framebuffer, rgb and alpha separately
+// colors are ARGB, so the alpha of the fragment shader output matters as soon as blending is on
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlendState {
+    pub color: BlendComponent,
+    pub alpha: BlendComponent,
+}
+
+impl BlendState {
+    pub const REPLACE: Self = Self {
+        color: BlendComponent::REPLACE,
+        alpha: BlendComponent::REPLACE,
+    };
+    // straight alpha, what textures and glTF `BLEND` materials use
+    pub const ALPHA_BLENDING: Self = Self {
+        color: BlendComponent::new(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha),
+        alpha: BlendComponent::OVER,
+    };
+    pub const PREMULTIPLIED_ALPHA_BLENDING: Self = Self {
+        color: BlendComponent::OVER,
+        alpha: BlendComponent::OVER,
+    };
+    // light adding up, for glows and particles, weighted by alpha so faded out ones vanish
+    pub const ADDITIVE: Self = Self {
+        color: BlendComponent::new(BlendFactor::SrcAlpha, BlendFactor::One),
+        alpha: BlendComponent::new(BlendFactor::One, BlendFactor::One),
+    };
+    // darkens what is already there, for tints and fake shadows, the alpha stays as it is
+    pub const MULTIPLY: Self = Self {
+        color: BlendComponent::new(BlendFactor::Dst, BlendFactor::Zero),
+        alpha: BlendComponent::new(BlendFactor::Zero, BlendFactor::One),
+    };
+
+    // the ARGB color left in the framebuffer after blending `src` over `dst`
+    pub fn blend(&self, src: u32, dst: u32) -> u32 {
+        let (s, d) = (argb_to_vec4(src), argb_to_vec4(dst));
+        let color = self.color.apply(s, d);
+        let alpha = self.alpha.apply(s, d);
+        vec4_to_argb(color.truncate().extend(alpha.w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn presets() {
+        let dst = from_u8_argb(255, 200, 100, 0);
+        let half_red = from_u8_argb(128, 255, 0, 0);
+        assert_eq!(BlendState::REPLACE.blend(half_red, dst), half_red);
+        assert_eq!(
+            BlendState::ALPHA_BLENDING.blend(half_red, dst),
+            from_u8_argb(255, 228, 50, 0)
+        );
+        // the same color premultiplied
+        assert_eq!(
+            BlendState::PREMULTIPLIED_ALPHA_BLENDING.blend(from_u8_argb(128, 128, 0, 0), dst),
+            from_u8_argb(255, 228, 50, 0)
+        );
+        assert_eq!(
+            BlendState::ADDITIVE.blend(half_red, dst),
+            from_u8_argb(255, 255, 100, 0)
+        );
+        assert_eq!(
+            BlendState::MULTIPLY.blend(from_u8_argb(0, 128, 255, 255), dst),
+            from_u8_argb(255, 100, 100, 0)
+        );
+        // a fully transparent fragment leaves the framebuffer alone
+        assert_eq!(BlendState::ALPHA_BLENDING.blend(0x00ff_ffff, dst), dst);
+
+        let max = BlendState {
+            color: BlendComponent {
+                operation: BlendOperation::Max,
+                ..BlendComponent::REPLACE
+            },
+            ..BlendState::REPLACE
+        };
+        assert_eq!(max.blend(half_red, dst), from_u8_argb(128, 255, 100, 0));
+    }
+
+    #[test]
+    fn layers_blend_in_draw_order() {
+        let quad = quad(-1.0, 1.0, 0.5);
+        let pipeline = PipelineState {
+            cull_mode: CullMode::None,
+            depth: DepthState {
+                compare: CompareFunction::Always,
+                write: false,
+                ..Default::default()
+            },
+            blend: Some(BlendState::ALPHA_BLENDING),
+            ..Default::default()
+        };
+        for samples in [1, 4] {
+            let mut framebuffer =
+                Framebuffer::with_samples(64, 32, SamplePattern::standard(samples));
+            framebuffer.clear(from_u8_argb(255, 0, 0, 0));
+            for color in [from_u8_argb(128, 255, 0, 0), from_u8_argb(128, 0, 0, 255)] {
+                draw(&quad, color, &pipeline, &mut framebuffer);
+            }
+            // the blue one on top, over what the red one left
+            let expected = from_u8_argb(255, 64, 0, 128);
+            assert!(framebuffer.color().iter().all(|&c| c == expected));
+        }
+    }
+}
@@ -1,12 +1,50 @@
 use crate::transform::Transform;
+use crate::CompareFunction;
 
-use glam::Mat4;
+use glam::{Mat4, Vec2};
+
+// which end of the 0..1 depth range the near plane maps to
+// `Reversed` puts the near plane at 1 and the far plane at 0, floats are densest close to 0,
+// which cancels out most of the 1/z falloff of perspective depth, so distant surfaces stay apart
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DepthRange {
+    #[default]
+    Standard,
+    Reversed,
+}
+
+impl DepthRange {
+    // the depth test actually done for a `DepthState::compare`, which is written for `Standard`
+    pub fn depth_compare(self, compare: CompareFunction) -> CompareFunction {
+        match self {
+            DepthRange::Standard => compare,
+            DepthRange::Reversed => compare.mirrored(),
+        }
+    }
+
+    // behind anything that can be drawn, the value to clear the depth attachment to
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthRange::Standard => f32::INFINITY,
+            DepthRange::Reversed => f32::NEG_INFINITY,
+        }
+    }
+
+    pub fn closest(self, a: f32, b: f32) -> f32 {
+        match self {
+            DepthRange::Standard => a.min(b),
+            DepthRange::Reversed => a.max(b),
+        }
+    }
+}
 
 pub struct Camera {
     pub frustum_near: f32,
+    // may be f32::INFINITY, best together with `DepthRange::Reversed`
     pub frustum_far: f32,
     pub fov: f32, // in radians
     pub aspect_ratio: f32,
+    pub depth_range: DepthRange,
     pub transform: Transform,
     pub speed: f32,
 }
@@ -18,6 +56,7 @@ impl Default for Camera {
             frustum_far: 100.0,
             fov: std::f32::consts::PI / 4.0,
             aspect_ratio: 1.0,
+            depth_range: DepthRange::Standard,
             transform: Transform::IDENTITY,
             speed: 10.0,
         }
@@ -26,12 +65,21 @@ impl Default for Camera {
 
 impl Camera {
     pub fn projection(&self) -> Mat4 {
-        Mat4::perspective_rh(
+        let (fov, aspect, near, far) = (
             self.fov,
             self.aspect_ratio,
             self.frustum_near,
             self.frustum_far,
-        )
+        );
+        match (self.depth_range, far.is_finite()) {
+            (DepthRange::Standard, true) => Mat4::perspective_rh(fov, aspect, near, far),
+            (DepthRange::Standard, false) => Mat4::perspective_infinite_rh(fov, aspect, near),
+            // swapping the planes maps near to 1 and far to 0
+            (DepthRange::Reversed, true) => Mat4::perspective_rh(fov, aspect, far, near),
+            (DepthRange::Reversed, false) => {
+                Mat4::perspective_infinite_reverse_rh(fov, aspect, near)
+            }
+        }
     }
 
     pub fn view(&self) -> Mat4 {
@@ -42,3 +90,140 @@ impl Camera {
         )
     }
 }
+
+// orthographic camera for sprites and text, one world unit is one pixel at a zoom of 1
+// y goes down like in the window, and the z of a position is its layer, from 0 at the back
+// to `layers` at the front, so depth testing keeps higher layers on top whatever the draw order
+pub struct Camera2D {
+    // the world position shown in the top left corner
+    pub position: Vec2,
+    pub zoom: f32,
+    // in pixels, the size of the framebuffer, or of the viewport when drawing into one
+    pub size: Vec2,
+    pub layers: f32,
+}
+
+impl Camera2D {
+    pub fn new(size: Vec2) -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+            size,
+            layers: 1024.0,
+        }
+    }
+
+    // layer 0 ends up at depth 1 and `layers` at depth 0
+    pub fn projection(&self) -> Mat4 {
+        let end = self.position + self.size / self.zoom;
+        Mat4::orthographic_rh(
+            self.position.x,
+            end.x,
+            self.position.y,
+            end.y,
+            -self.layers,
+            0.0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{Mat4, UVec3, Vec2, Vec3, Vec4};
+
+    fn camera(depth_range: DepthRange) -> Camera {
+        Camera {
+            depth_range,
+            frustum_far: f32::INFINITY,
+            ..Default::default()
+        }
+    }
+
+    // depth of a point `distance` in front of the camera, as it ends up in the depth attachment
+    fn depth(camera: &Camera, distance: f32) -> f32 {
+        let clip = camera.projection() * Vec4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn reversed_depth_keeps_distant_surfaces_apart() {
+        // surfaces a unit apart, further and further away
+        let distances: Vec<f32> = (0..2000).map(|i| 100.0 + i as f32 * 10.0).collect();
+        let collisions = |camera: &Camera| {
+            distances
+                .iter()
+                .filter(|&&d| depth(camera, d) == depth(camera, d + 1.0))
+                .count()
+        };
+        let standard = collisions(&camera(DepthRange::Standard));
+        let reversed = collisions(&camera(DepthRange::Reversed));
+        assert!(standard > distances.len() / 2, "{}", standard);
+        assert_eq!(reversed, 0);
+
+        // and both put the near plane where they say
+        let finite = |depth_range| Camera {
+            depth_range,
+            ..Default::default()
+        };
+        assert!(depth(&finite(DepthRange::Standard), 0.1).abs() < 1e-6);
+        assert!((depth(&finite(DepthRange::Standard), 100.0) - 1.0).abs() < 1e-6);
+        assert!((depth(&finite(DepthRange::Reversed), 0.1) - 1.0).abs() < 1e-6);
+        assert!(depth(&finite(DepthRange::Reversed), 100.0).abs() < 1e-6);
+    }
+
+    fn wall(distance: f32, color: Vec3) -> Mesh {
+        let vertex = |x: f32, y: f32| {
+            let pos = Vec4::new(x * distance, y * distance, -distance, 1.0);
+            Vertex::new(pos, Vec3::Z, color, Vec2::ZERO)
+        };
+        Mesh {
+            vertices: vec![
+                vertex(-1.0, -1.0),
+                vertex(1.0, -1.0),
+                vertex(-1.0, 1.0),
+                vertex(1.0, 1.0),
+            ],
+            triangle_indices: vec![UVec3::new(0, 1, 2), UVec3::new(1, 3, 2)],
+            ..Default::default()
+        }
+    }
+
+    // share of the pixels where the closer of two walls a unit apart ends up in front,
+    // the far one is drawn first so the close one has to pass the depth test
+    fn closer_wall_coverage(depth_range: DepthRange, distance: f32) -> f32 {
+        let camera = camera(depth_range);
+        let uniforms =
+            DefaultUniforms::new(camera.projection() * camera.view(), Mat4::IDENTITY, None);
+        // the same Less compare either way, the framebuffer turns it around for reversed depth
+        let pipeline = PipelineState::default();
+        let mut framebuffer = Framebuffer::new(64, 64).with_depth_range(depth_range);
+        framebuffer.clear(0);
+        for mesh in [wall(distance + 1.0, Vec3::X), wall(distance, Vec3::Y)] {
+            raster_mesh(
+                &mesh,
+                &DefaultShader,
+                &DefaultShader,
+                &uniforms,
+                &pipeline,
+                &RenderSettings::default(),
+                &mut framebuffer,
+            );
+        }
+        let green = framebuffer
+            .color()
+            .iter()
+            .filter(|&&c| (c >> 8) & 0xff > (c >> 16) & 0xff)
+            .count();
+        green as f32 / framebuffer.color().len() as f32
+    }
+
+    #[test]
+    fn reversed_depth_avoids_z_fighting() {
+        for distance in [10.0, 5000.0] {
+            assert_eq!(closer_wall_coverage(DepthRange::Reversed, distance), 1.0);
+        }
+        assert_eq!(closer_wall_coverage(DepthRange::Standard, 10.0), 1.0);
+        assert!(closer_wall_coverage(DepthRange::Standard, 5000.0) < 0.5);
+    }
+}
@@ -0,0 +1,416 @@
+use crate::{from_u8_rgb, ClipVertex, CullMode, FrontFace, Triangle, Varying};
+use glam::Vec4;
+
+// a triangle clipped against n planes has at most 3 + n vertices
+pub const MAX_CLIP_VERTICES: usize = 3 + CLIP_PLANES;
+pub const CLIP_PLANES: usize = 6;
+
+pub enum ClipResult<V> {
+    None,
+    // nothing had to be clipped
+    One(Triangle<ClipVertex<V>>),
+    // convex polygon, drawn as a triangle fan around the first vertex
+    Fan(Vec<ClipVertex<V>>),
+}
+
+// flat colors the fan triangles of a clipped polygon get in clip visualization mode
+pub fn clip_debug_color(fan_index: usize) -> u32 {
+    match fan_index % 3 {
+        0 => from_u8_rgb(255, 0, 0),
+        1 => from_u8_rgb(0, 255, 0),
+        _ => from_u8_rgb(0, 0, 255),
+    }
+}
+
+impl<V: Varying> ClipResult<V> {
+    pub fn is_clipped(&self) -> bool {
+        matches!(self, ClipResult::Fan(_))
+    }
+
+    pub fn for_each_triangle(&self, mut f: impl FnMut(&Triangle<ClipVertex<V>>)) {
+        match self {
+            ClipResult::None => {}
+            ClipResult::One(triangle) => f(triangle),
+            ClipResult::Fan(polygon) => {
+                for i in 1..polygon.len() - 1 {
+                    f(&Triangle::new(polygon[0], polygon[i], polygon[i + 1]));
+                }
+            }
+        }
+    }
+}
+
+// the frustum planes in clip space, a point is inside a plane if `plane.dot(pos) >= 0`
+// the side planes are pushed out by the guard band, near and far always clip
+// `DepthRange::Reversed` only swaps which of the depth planes is the near one, and with an
+// infinite far plane that one never cuts anything in front of the camera
+pub fn clip_planes(guard_band: f32) -> [Vec4; CLIP_PLANES] {
+    [
+        Vec4::new(0.0, 0.0, 1.0, 0.0),        // near: z >= 0, far when reversed
+        Vec4::new(0.0, 0.0, -1.0, 1.0),       // far: z <= w, near when reversed
+        Vec4::new(1.0, 0.0, 0.0, guard_band), // left: x >= -w
+        Vec4::new(-1.0, 0.0, 0.0, guard_band), // right: x <= w
+        Vec4::new(0.0, 1.0, 0.0, guard_band), // bottom: y >= -w
+        Vec4::new(0.0, -1.0, 0.0, guard_band), // top: y <= w
+    ]
+}
+
+// one bit per plane the position is outside of
+pub fn outcode(pos: Vec4, planes: &[Vec4; CLIP_PLANES]) -> u8 {
+    planes.iter().enumerate().fold(0, |code, (i, plane)| {
+        if plane.dot(pos) < 0.0 {
+            code | 1 << i
+        } else {
+            code
+        }
+    })
+}
+
+//View Frustum Culling
+pub fn cull_triangle_view_frustum<V>(triangle: &Triangle<ClipVertex<V>>) -> bool {
+    // cull tests against the 6 planes
+    if triangle.v0.pos.x > triangle.v0.pos.w
+        && triangle.v1.pos.x > triangle.v1.pos.w
+        && triangle.v2.pos.x > triangle.v2.pos.w
+    {
+        return true;
+    }
+    if triangle.v0.pos.x < -triangle.v0.pos.w
+        && triangle.v1.pos.x < -triangle.v1.pos.w
+        && triangle.v2.pos.x < -triangle.v2.pos.w
+    {
+        return true;
+    }
+    if triangle.v0.pos.y > triangle.v0.pos.w
+        && triangle.v1.pos.y > triangle.v1.pos.w
+        && triangle.v2.pos.y > triangle.v2.pos.w
+    {
+        return true;
+    }
+    if triangle.v0.pos.y < -triangle.v0.pos.w
+        && triangle.v1.pos.y < -triangle.v1.pos.w
+        && triangle.v2.pos.y < -triangle.v2.pos.w
+    {
+        return true;
+    }
+    if triangle.v0.pos.z > triangle.v0.pos.w
+        && triangle.v1.pos.z > triangle.v1.pos.w
+        && triangle.v2.pos.z > triangle.v2.pos.w
+    {
+        return true;
+    }
+    // in front of the near plane, or behind the far plane when reversed
+    if triangle.v0.pos.z < 0.0 && triangle.v1.pos.z < 0.0 && triangle.v2.pos.z < 0.0 {
+        return true;
+    }
+
+    false
+}
+
+// the part of the segment from `a` to `b` inside all the planes, as the range of the
+// interpolation factor from `a` to `b` that is left, None if it is entirely outside
+pub fn clip_segment(a: Vec4, b: Vec4, planes: &[Vec4; CLIP_PLANES]) -> Option<(f32, f32)> {
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for plane in planes {
+        let (da, db) = (plane.dot(a), plane.dot(b));
+        match (da >= 0.0, db >= 0.0) {
+            (true, true) => {}
+            (false, false) => return None,
+            (true, false) => t1 = t1.min(da / (da - db)),
+            (false, true) => t0 = t0.max(da / (da - db)),
+        }
+    }
+    (t0 <= t1).then_some((t0, t1))
+}
+
+// one Sutherland-Hodgman step, keeps the part of the polygon in front of the plane
+// the vertex order, and with it the winding, is preserved
+pub fn clip_polygon_against_plane<V: Varying>(
+    input: &[ClipVertex<V>],
+    plane: Vec4,
+    output: &mut Vec<ClipVertex<V>>,
+) {
+    output.clear();
+    for (i, current) in input.iter().enumerate() {
+        let next = &input[(i + 1) % input.len()];
+        let current_dist = plane.dot(current.pos);
+        let next_dist = plane.dot(next.pos);
+        if current_dist >= 0.0 {
+            output.push(*current);
+        }
+        if (current_dist >= 0.0) != (next_dist >= 0.0) {
+            let alpha = current_dist / (current_dist - next_dist);
+            output.push(current.lerp(next, alpha));
+        }
+    }
+}
+
+pub fn clip_cull_triangle<V: Varying>(
+    triangle: &Triangle<ClipVertex<V>>,
+    guard_band: f32,
+) -> ClipResult<V> {
+    if cull_triangle_view_frustum(triangle) {
+        return ClipResult::None;
+    }
+
+    let planes = clip_planes(guard_band);
+    let codes = [triangle.v0, triangle.v1, triangle.v2].map(|v| outcode(v.pos, &planes));
+    let crossed = codes[0] | codes[1] | codes[2];
+    if crossed == 0 {
+        // no clipping necessary
+        return ClipResult::One(*triangle);
+    }
+
+    let mut polygon = Vec::with_capacity(MAX_CLIP_VERTICES);
+    let mut scratch = Vec::with_capacity(MAX_CLIP_VERTICES);
+    polygon.extend_from_slice(&[triangle.v0, triangle.v1, triangle.v2]);
+    // only the planes that at least one vertex is outside of can cut the polygon
+    for (i, plane) in planes.iter().enumerate() {
+        if crossed & (1 << i) != 0 {
+            clip_polygon_against_plane(&polygon, *plane, &mut scratch);
+            std::mem::swap(&mut polygon, &mut scratch);
+            if polygon.len() < 3 {
+                return ClipResult::None;
+            }
+        }
+    }
+    ClipResult::Fan(polygon)
+}
+
+pub fn is_front_facing(signed_area: f32, front_face: FrontFace) -> bool {
+    match front_face {
+        FrontFace::CounterClockwise => signed_area > 0.0,
+        FrontFace::Clockwise => signed_area < 0.0,
+    }
+}
+
+// `signed_area` is the screen space edge function of the triangle,
+// positive for counter clockwise winding (y pointing the same way as in ndc)
+// degenerate triangles never produce any pixels so they are always culled
+pub fn cull_triangle_backface(
+    signed_area: f32,
+    cull_mode: CullMode,
+    front_face: FrontFace,
+) -> bool {
+    if signed_area == 0.0 || signed_area.is_nan() {
+        return true;
+    }
+    let front_facing = is_front_facing(signed_area, front_face);
+    match cull_mode {
+        CullMode::None => false,
+        CullMode::Front => front_facing,
+        CullMode::Back => !front_facing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{Mat4, UVec3, Vec2, Vec3, Vec4};
+
+    fn triangle(a: Vec4, b: Vec4, c: Vec4) -> Triangle<ClipVertex<()>> {
+        Triangle::new(
+            ClipVertex::new(a, ()),
+            ClipVertex::new(b, ()),
+            ClipVertex::new(c, ()),
+        )
+    }
+
+    #[test]
+    fn huge_triangle_is_clipped_to_frustum() {
+        let tri = triangle(
+            Vec4::new(-100.0, -100.0, 0.5, 1.0),
+            Vec4::new(100.0, -100.0, 0.5, 1.0),
+            Vec4::new(0.0, 100.0, 0.5, 1.0),
+        );
+        let mut triangles = 0;
+        clip_cull_triangle(&tri, 1.0).for_each_triangle(|clipped| {
+            triangles += 1;
+            for v in [clipped.v0, clipped.v1, clipped.v2] {
+                assert!(v.pos.x.abs() <= v.pos.w + 1e-5);
+                assert!(v.pos.y.abs() <= v.pos.w + 1e-5);
+            }
+        });
+        // the triangle covers the whole screen, so it is cut down to the viewport quad
+        assert_eq!(triangles, 2);
+    }
+
+    #[test]
+    fn guard_band_skips_side_clipping() {
+        let tri = triangle(
+            Vec4::new(-1.5, -0.5, 0.5, 1.0),
+            Vec4::new(0.5, -0.5, 0.5, 1.0),
+            Vec4::new(0.0, 0.5, 0.5, 1.0),
+        );
+        assert!(matches!(clip_cull_triangle(&tri, 1.0), ClipResult::Fan(_)));
+        assert!(matches!(clip_cull_triangle(&tri, 2.0), ClipResult::One(_)));
+    }
+
+    #[test]
+    fn guard_band_is_kept_in_range() {
+        let viewport = Viewport::full(Vec2::new(64.0, 64.0));
+        let settings = |guard_band| RenderSettings {
+            guard_band,
+            ..Default::default()
+        };
+        assert_eq!(settings(0.5).guard_band_for(&viewport), 1.0);
+        assert_eq!(settings(4.0).guard_band_for(&viewport), 4.0);
+        assert!(settings(1e9).guard_band_for(&viewport) < MAX_WINDOW_COORD / 64.0);
+
+        // neither clips inside the screen nor overflows the edge setup with a triangle that
+        // reaches far beyond it
+        let huge = Mesh {
+            vertices: [(-1e7, -1e7), (1e7, -1e7), (0.0, 1e7)]
+                .map(|(x, y)| {
+                    Vertex::new(Vec4::new(x, y, 0.5, 1.0), Vec3::Z, Vec3::ONE, Vec2::ZERO)
+                })
+                .to_vec(),
+            triangle_indices: vec![UVec3::new(0, 1, 2)],
+            ..Default::default()
+        };
+        let uniforms = DefaultUniforms::new(Mat4::IDENTITY, Mat4::IDENTITY, None);
+        let pipeline = PipelineState {
+            cull_mode: CullMode::None,
+            ..Default::default()
+        };
+        for guard_band in [0.5, 1e9] {
+            let mut framebuffer = Framebuffer::new(64, 64);
+            framebuffer.clear(0);
+            raster_mesh(
+                &huge,
+                &DefaultShader,
+                &DefaultShader,
+                &uniforms,
+                &pipeline,
+                &settings(guard_band),
+                &mut framebuffer,
+            );
+            assert!(framebuffer.color().iter().all(|&c| c != 0));
+        }
+    }
+
+    #[test]
+    fn near_and_far_always_clip() {
+        let tri = triangle(
+            Vec4::new(0.0, 0.0, -1.0, 1.0),
+            Vec4::new(0.5, 0.0, 2.0, 1.0),
+            Vec4::new(0.0, 0.5, 0.5, 1.0),
+        );
+        match clip_cull_triangle(&tri, 10.0) {
+            ClipResult::Fan(polygon) => {
+                assert!(polygon.iter().all(|v| v.pos.z >= 0.0 && v.pos.z <= v.pos.w));
+            }
+            _ => panic!("expected the triangle to be clipped"),
+        }
+    }
+
+    #[test]
+    fn reversed_depth_clips_at_the_near_plane() {
+        for frustum_far in [100.0, f32::INFINITY] {
+            let camera = Camera {
+                depth_range: DepthRange::Reversed,
+                frustum_far,
+                ..Default::default()
+            };
+            let project = |z: f32| {
+                let pos = camera.projection() * Vec4::new(0.0, 0.0, z, 1.0);
+                move |x: f32, y: f32| pos + Vec4::new(x, y, 0.0, 0.0)
+            };
+
+            // reaching from behind the camera into the frustum
+            let (behind, inside) = (project(1.0), project(-5.0));
+            let tri = triangle(behind(0.0, 0.0), inside(0.5, 0.0), inside(0.0, 0.5));
+            match clip_cull_triangle(&tri, 1.0) {
+                ClipResult::Fan(polygon) => {
+                    // w is the distance in front of the camera
+                    assert!(polygon
+                        .iter()
+                        .all(|v| v.pos.w >= camera.frustum_near - 1e-5));
+                }
+                _ => panic!("expected the triangle to be clipped"),
+            }
+
+            let tri = triangle(behind(0.0, 0.0), behind(0.5, 0.0), behind(0.0, 0.5));
+            assert!(matches!(clip_cull_triangle(&tri, 1.0), ClipResult::None));
+
+            // far away only the finite far plane gets in the way
+            let distant = project(-1e6);
+            let tri = triangle(distant(0.0, 0.0), distant(1e5, 0.0), distant(0.0, 1e5));
+            let visible = matches!(clip_cull_triangle(&tri, 1.0), ClipResult::One(_));
+            assert_eq!(visible, frustum_far.is_infinite());
+        }
+    }
+
+    #[test]
+    fn segments_are_cut_to_the_frustum() {
+        let planes = clip_planes(1.0);
+        let (a, b) = (
+            Vec4::new(-3.0, 0.0, 0.5, 1.0),
+            Vec4::new(1.0, 0.0, 0.5, 1.0),
+        );
+        assert_eq!(clip_segment(a, b, &planes), Some((0.5, 1.0)));
+        assert_eq!(clip_segment(b, a, &planes), Some((0.0, 0.5)));
+        let behind = Vec4::new(0.0, 0.0, -1.0, 1.0);
+        assert_eq!(clip_segment(behind, behind + Vec4::X, &planes), None);
+    }
+
+    #[test]
+    fn clip_visualization_is_opt_in() {
+        // a quad reaching behind the camera, so near clipping kicks in
+        let vertex = |x: f32, y: f32, z: f32| {
+            Vertex::new(Vec4::new(x, y, z, 1.0), Vec3::Z, Vec3::ONE, Vec2::ZERO)
+        };
+        let quad = Mesh {
+            triangle_indices: vec![UVec3::new(0, 2, 1), UVec3::new(2, 3, 1)],
+            vertices: vec![
+                vertex(-0.3, -0.3, -1.0),
+                vertex(-0.3, 0.3, -1.0),
+                vertex(0.5, -0.3, 1.0),
+                vertex(0.5, 0.3, 1.0),
+            ],
+            ..Default::default()
+        };
+        let camera = Camera::default();
+        let uniforms = DefaultUniforms::new(camera.projection(), Mat4::IDENTITY, None);
+        let render = |settings: &RenderSettings| {
+            let mut framebuffer = Framebuffer::new(32, 32);
+            raster_mesh(
+                &quad,
+                &DefaultShader,
+                &DefaultShader,
+                &uniforms,
+                &PipelineState::default(),
+                settings,
+                &mut framebuffer,
+            );
+            framebuffer.color().to_vec()
+        };
+
+        let debug_colors: Vec<u32> = (0..3).map(clip_debug_color).collect();
+        let shaded = render(&RenderSettings::default());
+        assert!(shaded.iter().any(|&c| c != 0));
+        assert!(shaded.iter().all(|c| !debug_colors.contains(c)));
+
+        let tinted = render(&RenderSettings {
+            clip_visualization: true,
+            ..Default::default()
+        });
+        assert!(tinted.iter().all(|c| *c == 0 || debug_colors.contains(c)));
+        assert!(tinted.iter().any(|&c| c != 0));
+    }
+
+    #[test]
+    fn backface_culling_follows_winding() {
+        let ccw = 1.0;
+        let cw = -1.0;
+        use CullMode::*;
+        use FrontFace::*;
+        assert!(!cull_triangle_backface(ccw, Back, CounterClockwise));
+        assert!(cull_triangle_backface(cw, Back, CounterClockwise));
+        assert!(cull_triangle_backface(ccw, Back, Clockwise));
+        assert!(cull_triangle_backface(ccw, Front, CounterClockwise));
+        assert!(!cull_triangle_backface(cw, None, CounterClockwise));
+        // degenerate triangles are always dropped
+        assert!(cull_triangle_backface(0.0, None, CounterClockwise));
+    }
+}
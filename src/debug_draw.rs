@@ -0,0 +1,272 @@
+use crate::{from_u8_rgb, PipelineState, Transform};
+use crate::{raster_clip_line, Camera, CompareFunction, DepthState, Framebuffer};
+
+use glam::{Mat4, Vec3};
+
+// segments per circle of `sphere`
+const CIRCLE_SEGMENTS: usize = 32;
+// how far `frustum` draws the sides of a camera with an infinite far plane, in near plane distances
+const INFINITE_FAR_SCALE: f32 = 1000.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DebugLine {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub color: u32,
+}
+
+// world space lines collected during a frame and drawn all at once in `render`
+// like `Font::text`, nothing is drawn until then and the list starts over afterwards
+pub struct DebugDraw {
+    pub(crate) to_render: Vec<DebugLine>,
+    // drawn on top of everything instead of being hidden behind closer geometry
+    pub overlay: bool,
+    pub line_width: f32,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self {
+            to_render: Vec::new(),
+            overlay: false,
+            line_width: 1.0,
+        }
+    }
+}
+
+impl DebugDraw {
+    pub fn lines(&self) -> &[DebugLine] {
+        &self.to_render
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: u32) {
+        self.to_render.push(DebugLine { a, b, color });
+    }
+
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: u32) {
+        let corner = |i: usize| {
+            Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        self.box_edges(corner, color);
+    }
+
+    // three circles, one around each axis
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: u32) {
+        for (u, v) in [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)] {
+            let point = |i: usize| {
+                let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                center + (u * angle.cos() + v * angle.sin()) * radius
+            };
+            for i in 0..CIRCLE_SEGMENTS {
+                self.line(point(i), point(i + 1), color);
+            }
+        }
+    }
+
+    // x red, y green, z blue, scaled along with the transform
+    pub fn axes(&mut self, transform: &Transform, length: f32) {
+        let local = transform.local();
+        let origin = local.transform_point3(Vec3::ZERO);
+        for (axis, color) in [
+            (Vec3::X, from_u8_rgb(255, 0, 0)),
+            (Vec3::Y, from_u8_rgb(0, 255, 0)),
+            (Vec3::Z, from_u8_rgb(0, 0, 255)),
+        ] {
+            self.line(origin, local.transform_point3(axis * length), color);
+        }
+    }
+
+    // the volume another camera sees, independent of its depth range
+    pub fn frustum(&mut self, camera: &Camera, color: u32) {
+        let transform = &camera.transform;
+        let far = if camera.frustum_far.is_finite() {
+            camera.frustum_far
+        } else {
+            camera.frustum_near * INFINITE_FAR_SCALE
+        };
+        let half_height = (camera.fov * 0.5).tan();
+        let half_width = half_height * camera.aspect_ratio;
+        let corner = |i: usize| {
+            let distance = if i & 4 == 0 { camera.frustum_near } else { far };
+            let x = if i & 1 == 0 { -half_width } else { half_width };
+            let y = if i & 2 == 0 {
+                -half_height
+            } else {
+                half_height
+            };
+            transform.translation
+                + (transform.forward() + transform.right() * x + transform.up() * y) * distance
+        };
+        self.box_edges(corner, color);
+    }
+
+    // on the xz plane around the origin, `cells` squares of `spacing` along each side
+    pub fn grid(&mut self, cells: u32, spacing: f32, color: u32) {
+        let half = cells as f32 * spacing * 0.5;
+        for i in 0..=cells {
+            let offset = i as f32 * spacing - half;
+            self.line(
+                Vec3::new(offset, 0.0, -half),
+                Vec3::new(offset, 0.0, half),
+                color,
+            );
+            self.line(
+                Vec3::new(-half, 0.0, offset),
+                Vec3::new(half, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    // the 12 edges between 8 corners, bit 0 of the index picks x, bit 1 y and bit 2 z
+    fn box_edges(&mut self, corner: impl Fn(usize) -> Vec3, color: u32) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    // depth tested against what was drawn before, but never writing depth,
+    // so the lines don't hide each other or anything drawn after them
+    pub fn render(&mut self, view_projection: Mat4, framebuffer: &mut Framebuffer) {
+        let pipeline = PipelineState {
+            depth: DepthState {
+                compare: if self.overlay {
+                    CompareFunction::Always
+                } else {
+                    CompareFunction::Less
+                },
+                write: false,
+                ..Default::default()
+            },
+            line_width: self.line_width,
+            ..Default::default()
+        };
+        for line in self.to_render.drain(..) {
+            raster_clip_line(
+                view_projection * line.a.extend(1.0),
+                view_projection * line.b.extend(1.0),
+                line.color,
+                &pipeline,
+                framebuffer,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{Mat4, Quat, Vec3};
+
+    #[test]
+    fn primitives() {
+        let mut debug = DebugDraw::default();
+        debug.aabb(Vec3::ZERO, Vec3::ONE, 0xff);
+        assert_eq!(debug.lines().len(), 12);
+        // every edge is axis aligned and one unit long
+        assert!(debug
+            .lines()
+            .iter()
+            .all(|line| (line.b - line.a).length() == 1.0));
+
+        let mut debug = DebugDraw::default();
+        debug.sphere(Vec3::ONE, 2.0, 0xff);
+        assert!(debug
+            .lines()
+            .iter()
+            .all(|line| ((line.a - Vec3::ONE).length() - 2.0).abs() < 1e-5));
+
+        let mut debug = DebugDraw::default();
+        let transform = Transform::new(
+            Vec3::new(1.0, 0.0, 0.0),
+            Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            Vec3::ONE,
+        );
+        debug.axes(&transform, 1.0);
+        let x = debug.lines()[0];
+        assert_eq!(x.color, from_u8_rgb(255, 0, 0));
+        assert!((x.b - Vec3::new(1.0, 1.0, 0.0)).length() < 1e-5);
+
+        let mut debug = DebugDraw::default();
+        debug.grid(4, 1.0, 0xff);
+        assert_eq!(debug.lines().len(), 10);
+        assert_eq!(debug.lines()[0].a, Vec3::new(-2.0, 0.0, -2.0));
+    }
+
+    #[test]
+    fn frustum_corners() {
+        let camera = Camera {
+            frustum_near: 1.0,
+            frustum_far: 10.0,
+            fov: std::f32::consts::FRAC_PI_2,
+            ..Default::default()
+        };
+        let mut debug = DebugDraw::default();
+        debug.frustum(&camera, 0xff);
+        assert_eq!(debug.lines().len(), 12);
+        // every corner lands on the edge of the projected near or far plane
+        let view_projection = camera.projection() * camera.view();
+        for line in debug.lines() {
+            for point in [line.a, line.b] {
+                let ndc = view_projection.project_point3(point);
+                assert!((ndc.x.abs() - 1.0).abs() < 1e-4);
+                assert!((ndc.y.abs() - 1.0).abs() < 1e-4);
+                assert!(ndc.z.abs() < 1e-4 || (ndc.z - 1.0).abs() < 1e-4);
+            }
+        }
+
+        // an infinite far plane still gives something to look at
+        let camera = Camera {
+            frustum_far: f32::INFINITY,
+            depth_range: DepthRange::Reversed,
+            ..camera
+        };
+        let mut debug = DebugDraw::default();
+        debug.frustum(&camera, 0xff);
+        assert!(debug.lines().iter().all(|line| line.b.is_finite()));
+    }
+
+    #[test]
+    fn depth_tested_or_overlay() {
+        let red = from_u8_rgb(255, 0, 0);
+        for (depth_range, overlay) in [
+            (DepthRange::Standard, false),
+            (DepthRange::Reversed, false),
+            (DepthRange::Standard, true),
+        ] {
+            let mut framebuffer = Framebuffer::new(32, 32).with_depth_range(depth_range);
+            framebuffer.clear(0);
+            // something between the camera and the line covering the left half
+            let closest = depth_range.closest(0.2, 0.8);
+            for y in 0..32 {
+                for x in 0..16 {
+                    framebuffer.set_depth(x, y, closest);
+                }
+            }
+            let mut debug = DebugDraw {
+                overlay,
+                ..Default::default()
+            };
+            debug.line(Vec3::new(-1.0, 0.0, 0.5), Vec3::new(1.0, 0.0, 0.5), red);
+            debug.render(Mat4::IDENTITY, &mut framebuffer);
+            assert!(debug.lines().is_empty());
+
+            let lit = framebuffer.color().iter().filter(|&&c| c == red).count();
+            assert_eq!(lit, if overlay { 32 } else { 16 });
+            // the depth of the occluder is left alone
+            assert_eq!(framebuffer.depth_at(4, 16), Some(closest));
+            assert_eq!(
+                framebuffer.depth_at(20, 16),
+                Some(depth_range.clear_value())
+            );
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{DepthRange, Framebuffer};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// file formats a framebuffer can be saved as, picked from the file extension
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    // binary netpbm, P6 for color and P5 for grayscale, trivial to read back from any tool
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "ppm" | "pgm" => Some(Self::Ppm),
+            _ => None,
+        }
+    }
+}
+
+// 8 bit rgb, row by row from the top, multisampled framebuffers are resolved first
+pub fn color_to_rgb(framebuffer: &Framebuffer) -> Vec<u8> {
+    let rgb = |color: &[u32]| -> Vec<u8> {
+        color
+            .iter()
+            .flat_map(|&c| {
+                let [_, r, g, b] = c.to_be_bytes();
+                [r, g, b]
+            })
+            .collect()
+    };
+    if framebuffer.samples().count() == 1 {
+        return rgb(framebuffer.color());
+    }
+    let mut resolved = Framebuffer::new(framebuffer.width(), framebuffer.height());
+    framebuffer.resolve(&mut resolved);
+    rgb(resolved.color())
+}
+
+// depth mapped to 8 bit gray, the closest written depth is black and the farthest white
+// pixels nothing was drawn to are white as well, multisampled pixels show their closest sample
+// the depth range of the framebuffer decides which end is close
+pub fn depth_to_gray(framebuffer: &Framebuffer) -> Vec<u8> {
+    let range = framebuffer.depth_range();
+    let depth: Vec<f32> = framebuffer
+        .depth()
+        .chunks_exact(framebuffer.samples().count())
+        .map(|samples| {
+            samples
+                .iter()
+                .copied()
+                .fold(range.clear_value(), |a, b| range.closest(a, b))
+        })
+        .collect();
+    let (min, max) = depth
+        .iter()
+        .filter(|z| z.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &z| {
+            (min.min(z), max.max(z))
+        });
+    let extent = (max - min).max(f32::EPSILON);
+    depth
+        .iter()
+        .map(|&z| {
+            if z.is_finite() {
+                let far = match range {
+                    DepthRange::Standard => z - min,
+                    DepthRange::Reversed => max - z,
+                };
+                (far / extent * 255.0).round() as u8
+            } else {
+                255
+            }
+        })
+        .collect()
+}
+
+// `channels` is 3 for rgb and 1 for gray
+fn write_image(
+    path: &Path,
+    width: usize,
+    height: usize,
+    channels: usize,
+    data: &[u8],
+) -> io::Result<()> {
+    let format = ImageFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported image format: {}", path.display()),
+        )
+    })?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        ImageFormat::Png => {
+            let mut encoder = png::Encoder::new(&mut writer, width as u32, height as u32);
+            encoder.set_color(if channels == 3 {
+                png::ColorType::Rgb
+            } else {
+                png::ColorType::Grayscale
+            });
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.write_header()?.write_image_data(data)?;
+        }
+        ImageFormat::Ppm => {
+            let magic = if channels == 3 { "P6" } else { "P5" };
+            write!(writer, "{}\n{} {}\n255\n", magic, width, height)?;
+            writer.write_all(data)?;
+        }
+    }
+    writer.flush()
+}
+
+// writes the color attachment, the format follows the extension: .png, .ppm
+pub fn save_color(framebuffer: &Framebuffer, path: &Path) -> io::Result<()> {
+    write_image(
+        path,
+        framebuffer.width(),
+        framebuffer.height(),
+        3,
+        &color_to_rgb(framebuffer),
+    )
+}
+
+// writes the depth attachment as a grayscale image, see `depth_to_gray`: .png, .pgm
+pub fn save_depth(framebuffer: &Framebuffer, path: &Path) -> io::Result<()> {
+    write_image(
+        path,
+        framebuffer.width(),
+        framebuffer.height(),
+        1,
+        &depth_to_gray(framebuffer),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::path::{Path, PathBuf};
+
+    fn gradient() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                framebuffer.set_color(x, y, from_u8_rgb(x as u8 * 100, y as u8 * 200, 7));
+            }
+        }
+        framebuffer.set_depth(0, 0, 0.25);
+        framebuffer.set_depth(1, 0, 0.5);
+        framebuffer.set_depth(2, 1, 0.75);
+        framebuffer
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rusterizer_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn depth_is_normalized() {
+        let gray = depth_to_gray(&gradient());
+        assert_eq!(gray, [0, 128, 255, 255, 255, 255]);
+
+        let mut reversed = gradient().with_depth_range(DepthRange::Reversed);
+        reversed.set_depth(0, 0, 0.75);
+        reversed.set_depth(1, 0, 0.5);
+        reversed.set_depth(2, 1, 0.25);
+        let gray = depth_to_gray(&reversed);
+        assert_eq!(gray, [0, 128, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn ppm_layout() {
+        let path = temp_path("export.ppm");
+        save_color(&gradient(), &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(&bytes[header.len()..], color_to_rgb(&gradient()));
+        assert_eq!(&bytes[header.len() + 3..header.len() + 6], [100, 0, 7]);
+    }
+
+    fn read_png(path: &Path) -> (u32, u32, Vec<u8>) {
+        let decoder = png::Decoder::new(std::fs::File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        data.truncate(info.buffer_size());
+        std::fs::remove_file(path).unwrap();
+        (info.width, info.height, data)
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let framebuffer = gradient();
+        let path = temp_path("color.png");
+        save_color(&framebuffer, &path).unwrap();
+        assert_eq!(read_png(&path), (3, 2, color_to_rgb(&framebuffer)));
+
+        let path = temp_path("depth.png");
+        save_depth(&framebuffer, &path).unwrap();
+        assert_eq!(read_png(&path), (3, 2, depth_to_gray(&framebuffer)));
+    }
+
+    #[test]
+    fn unknown_extension_is_an_error() {
+        let error = save_color(&gradient(), &temp_path("export.bmp")).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
@@ -1,6 +1,7 @@
 use glam::{UVec3, Vec2, Vec3, Vec4};
-use rusterizer::Vertex;
+use rusterizer::{BlendState, Camera2D, CompareFunction, DepthState, PipelineState, Vertex};
 
+use crate::Framebuffer;
 use crate::Mesh;
 use crate::Texture;
 use std::collections::HashMap;
@@ -16,99 +17,99 @@ pub struct Font {
 impl Default for Font {
     fn default() -> Self {
         let mut map = HashMap::new();
-        map.insert(char::from(' '), 0);
-        map.insert(char::from('!'), 1);
-        map.insert(char::from('"'), 2);
-        map.insert(char::from('#'), 3);
-        map.insert(char::from('$'), 4);
-        map.insert(char::from('%'), 5);
-        map.insert(char::from('&'), 6);
-        map.insert(char::from('\''), 7);
-        map.insert(char::from('('), 8);
-        map.insert(char::from(')'), 9);
-        map.insert(char::from('*'), 10);
-        map.insert(char::from('+'), 11);
-        map.insert(char::from(','), 12);
-        map.insert(char::from('-'), 13);
-        map.insert(char::from('.'), 14);
-        map.insert(char::from('/'), 15);
-        map.insert(char::from('0'), 16);
-        map.insert(char::from('1'), 17);
-        map.insert(char::from('2'), 18);
-        map.insert(char::from('3'), 19);
-        map.insert(char::from('4'), 20);
-        map.insert(char::from('5'), 21);
-        map.insert(char::from('6'), 22);
-        map.insert(char::from('7'), 23);
-        map.insert(char::from('8'), 24);
-        map.insert(char::from('9'), 25);
-        map.insert(char::from(':'), 26);
-        map.insert(char::from(';'), 27);
-        map.insert(char::from('<'), 28);
-        map.insert(char::from('='), 29);
-        map.insert(char::from('>'), 30);
-        map.insert(char::from('?'), 31);
-        map.insert(char::from('@'), 32);
-        map.insert(char::from('a'), 33);
-        map.insert(char::from('A'), 33);
-        map.insert(char::from('b'), 34);
-        map.insert(char::from('B'), 34);
-        map.insert(char::from('c'), 35);
-        map.insert(char::from('C'), 35);
-        map.insert(char::from('d'), 36);
-        map.insert(char::from('D'), 36);
-        map.insert(char::from('e'), 37);
-        map.insert(char::from('E'), 37);
-        map.insert(char::from('f'), 38);
-        map.insert(char::from('F'), 38);
-        map.insert(char::from('g'), 39);
-        map.insert(char::from('G'), 39);
-        map.insert(char::from('h'), 40);
-        map.insert(char::from('H'), 40);
-        map.insert(char::from('i'), 41);
-        map.insert(char::from('I'), 41);
-        map.insert(char::from('j'), 42);
-        map.insert(char::from('J'), 42);
-        map.insert(char::from('k'), 43);
-        map.insert(char::from('K'), 43);
-        map.insert(char::from('l'), 44);
-        map.insert(char::from('L'), 44);
-        map.insert(char::from('m'), 45);
-        map.insert(char::from('M'), 45);
-        map.insert(char::from('n'), 46);
-        map.insert(char::from('N'), 46);
-        map.insert(char::from('o'), 47);
-        map.insert(char::from('O'), 47);
-        map.insert(char::from('p'), 48);
-        map.insert(char::from('P'), 48);
-        map.insert(char::from('q'), 49);
-        map.insert(char::from('Q'), 49);
-        map.insert(char::from('r'), 50);
-        map.insert(char::from('R'), 50);
-        map.insert(char::from('s'), 51);
-        map.insert(char::from('S'), 51);
-        map.insert(char::from('t'), 52);
-        map.insert(char::from('T'), 52);
-        map.insert(char::from('u'), 53);
-        map.insert(char::from('U'), 53);
-        map.insert(char::from('v'), 54);
-        map.insert(char::from('V'), 54);
-        map.insert(char::from('w'), 55);
-        map.insert(char::from('W'), 55);
-        map.insert(char::from('x'), 56);
-        map.insert(char::from('X'), 56);
-        map.insert(char::from('y'), 57);
-        map.insert(char::from('Y'), 57);
-        map.insert(char::from('z'), 58);
-        map.insert(char::from('Z'), 58);
-        map.insert(char::from('['), 59);
-        map.insert(char::from('\\'), 60);
-        map.insert(char::from(']'), 61);
-        map.insert(char::from('^'), 62);
-        map.insert(char::from('_'), 63);
-        map.insert(char::from('|'), 92);
-        map.insert(char::from('~'), 94);
-        map.insert(char::from('á'), 102);
+        map.insert(' ', 0);
+        map.insert('!', 1);
+        map.insert('"', 2);
+        map.insert('#', 3);
+        map.insert('$', 4);
+        map.insert('%', 5);
+        map.insert('&', 6);
+        map.insert('\'', 7);
+        map.insert('(', 8);
+        map.insert(')', 9);
+        map.insert('*', 10);
+        map.insert('+', 11);
+        map.insert(',', 12);
+        map.insert('-', 13);
+        map.insert('.', 14);
+        map.insert('/', 15);
+        map.insert('0', 16);
+        map.insert('1', 17);
+        map.insert('2', 18);
+        map.insert('3', 19);
+        map.insert('4', 20);
+        map.insert('5', 21);
+        map.insert('6', 22);
+        map.insert('7', 23);
+        map.insert('8', 24);
+        map.insert('9', 25);
+        map.insert(':', 26);
+        map.insert(';', 27);
+        map.insert('<', 28);
+        map.insert('=', 29);
+        map.insert('>', 30);
+        map.insert('?', 31);
+        map.insert('@', 32);
+        map.insert('a', 33);
+        map.insert('A', 33);
+        map.insert('b', 34);
+        map.insert('B', 34);
+        map.insert('c', 35);
+        map.insert('C', 35);
+        map.insert('d', 36);
+        map.insert('D', 36);
+        map.insert('e', 37);
+        map.insert('E', 37);
+        map.insert('f', 38);
+        map.insert('F', 38);
+        map.insert('g', 39);
+        map.insert('G', 39);
+        map.insert('h', 40);
+        map.insert('H', 40);
+        map.insert('i', 41);
+        map.insert('I', 41);
+        map.insert('j', 42);
+        map.insert('J', 42);
+        map.insert('k', 43);
+        map.insert('K', 43);
+        map.insert('l', 44);
+        map.insert('L', 44);
+        map.insert('m', 45);
+        map.insert('M', 45);
+        map.insert('n', 46);
+        map.insert('N', 46);
+        map.insert('o', 47);
+        map.insert('O', 47);
+        map.insert('p', 48);
+        map.insert('P', 48);
+        map.insert('q', 49);
+        map.insert('Q', 49);
+        map.insert('r', 50);
+        map.insert('R', 50);
+        map.insert('s', 51);
+        map.insert('S', 51);
+        map.insert('t', 52);
+        map.insert('T', 52);
+        map.insert('u', 53);
+        map.insert('U', 53);
+        map.insert('v', 54);
+        map.insert('V', 54);
+        map.insert('w', 55);
+        map.insert('W', 55);
+        map.insert('x', 56);
+        map.insert('X', 56);
+        map.insert('y', 57);
+        map.insert('Y', 57);
+        map.insert('z', 58);
+        map.insert('Z', 58);
+        map.insert('[', 59);
+        map.insert('\\', 60);
+        map.insert(']', 61);
+        map.insert('^', 62);
+        map.insert('_', 63);
+        map.insert('|', 92);
+        map.insert('~', 94);
+        map.insert('á', 102);
 
         let font_texture = Texture::load(Path::new("assets/fonts/outline_cute.png"));
 
@@ -182,6 +183,7 @@ impl Font {
                 self.to_render.push(Mesh {
                     triangle_indices: vec![UVec3::new(0, 1, 2), UVec3::new(2, 1, 3)],
                     vertices: vec![v0, v1, v2, v3],
+                    ..Default::default()
                 });
             } else {
                 println!("Symbol \"{}\" is not supported yet!", char);
@@ -195,10 +197,24 @@ impl Font {
         }
     }
 
-    pub fn render(&mut self, buffer: &mut Vec<u32>, z_buffer: &mut Vec<f32>, viewport_size: Vec2) {
-        for quad in &self.to_render {
-            rusterizer::raster_mesh_2d(quad, Some(&self.texture), buffer, z_buffer, viewport_size);
-        }
+    pub fn render(&mut self, framebuffer: &mut Framebuffer) {
+        // an overlay, drawn over whatever the depth attachment holds, in either depth range
+        let pipeline = PipelineState {
+            depth: DepthState {
+                compare: CompareFunction::Always,
+                write: false,
+                ..Default::default()
+            },
+            blend: Some(BlendState::ALPHA_BLENDING),
+            ..Default::default()
+        };
+        rusterizer::raster_layers_2d(
+            &self.to_render,
+            Some(&self.texture),
+            &Camera2D::new(framebuffer.size()),
+            &pipeline,
+            framebuffer,
+        );
         self.to_render.clear();
     }
 }
@@ -0,0 +1,325 @@
+use crate::{
+    coords_to_index, oit, DepthRange, Fragment, PipelineState, SamplePattern, StencilFace,
+    StencilState,
+};
+use glam::Vec2;
+
+// owns everything a frame is drawn into, all attachments always have the same dimensions
+// multisampled framebuffers keep `samples.count()` values per pixel, the samples of a pixel
+// are next to each other, and have to be resolved into a single sampled one to be presented
+// `depth_range` has to match the projection drawn with, it decides what depth is cleared to
+// and which way the depth compare of a pipeline goes
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    samples: SamplePattern,
+    depth_range: DepthRange,
+    color: Vec<u32>,
+    depth: Vec<f32>,
+    stencil: Option<Vec<u8>>,
+    fragments: Option<Vec<Vec<Fragment>>>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_samples(width, height, SamplePattern::single())
+    }
+
+    pub fn with_samples(width: usize, height: usize, samples: SamplePattern) -> Self {
+        assert!(width > 0 && height > 0, "framebuffer can't be empty");
+        let len = width * height * samples.count();
+        Self {
+            width,
+            height,
+            samples,
+            depth_range: DepthRange::Standard,
+            color: vec![0; len],
+            depth: vec![DepthRange::Standard.clear_value(); len],
+            stencil: None,
+            fragments: None,
+        }
+    }
+
+    // also clears depth to the clear value of `depth_range`
+    pub fn with_depth_range(mut self, depth_range: DepthRange) -> Self {
+        self.depth_range = depth_range;
+        self.clear_depth(depth_range.clear_value());
+        self
+    }
+
+    pub fn with_stencil(mut self) -> Self {
+        self.stencil = Some(vec![0; self.color.len()]);
+        self
+    }
+
+    // an A-buffer, a list of transparent fragments per sample, filled by pipelines drawing
+    // `order_independent` and composited by `resolve_fragments`
+    pub fn with_fragment_lists(mut self) -> Self {
+        self.fragments = Some(vec![Vec::new(); self.color.len()]);
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // the viewport the pipeline maps normalized device coordinates to
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32)
+    }
+
+    pub fn samples(&self) -> &SamplePattern {
+        &self.samples
+    }
+
+    pub fn depth_range(&self) -> DepthRange {
+        self.depth_range
+    }
+
+    pub fn color(&self) -> &[u32] {
+        &self.color
+    }
+
+    pub fn color_mut(&mut self) -> &mut [u32] {
+        &mut self.color
+    }
+
+    pub fn depth(&self) -> &[f32] {
+        &self.depth
+    }
+
+    pub fn depth_mut(&mut self) -> &mut [f32] {
+        &mut self.depth
+    }
+
+    pub fn stencil(&self) -> Option<&[u8]> {
+        self.stencil.as_deref()
+    }
+
+    pub fn stencil_mut(&mut self) -> Option<&mut [u8]> {
+        self.stencil.as_deref_mut()
+    }
+
+    pub fn fragments(&self) -> Option<&[Vec<Fragment>]> {
+        self.fragments.as_deref()
+    }
+
+    // index of the first sample of a pixel, None outside of the framebuffer
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        (x < self.width && y < self.height)
+            .then(|| coords_to_index(x, y, self.width) * self.samples.count())
+    }
+
+    pub fn color_at(&self, x: usize, y: usize) -> Option<u32> {
+        self.index(x, y).map(|id| self.color[id])
+    }
+
+    pub fn depth_at(&self, x: usize, y: usize) -> Option<f32> {
+        self.index(x, y).map(|id| self.depth[id])
+    }
+
+    // writes every sample of the pixel, does nothing outside of the framebuffer
+    pub fn set_color(&mut self, x: usize, y: usize, color: u32) {
+        if let Some(id) = self.index(x, y) {
+            self.color[id..id + self.samples.count()].fill(color);
+        }
+    }
+
+    pub fn set_depth(&mut self, x: usize, y: usize, depth: f32) {
+        if let Some(id) = self.index(x, y) {
+            self.depth[id..id + self.samples.count()].fill(depth);
+        }
+    }
+
+    pub fn clear_color(&mut self, color: u32) {
+        self.color.fill(color);
+    }
+
+    pub fn clear_depth(&mut self, depth: f32) {
+        self.depth.fill(depth);
+    }
+
+    pub fn clear_stencil(&mut self, value: u8) {
+        if let Some(stencil) = &mut self.stencil {
+            stencil.fill(value);
+        }
+    }
+
+    // color to `color`, depth to behind everything, stencil to 0, fragment lists empty
+    pub fn clear(&mut self, color: u32) {
+        self.clear_color(color);
+        self.clear_depth(self.depth_range.clear_value());
+        self.clear_stencil(0);
+        for list in self.fragments.iter_mut().flatten() {
+            list.clear();
+        }
+    }
+
+    // composites the transparent fragments over the color drawn so far, after the opaque pass
+    pub fn resolve_fragments(&mut self) {
+        let Some(fragments) = &mut self.fragments else {
+            return;
+        };
+        for ((color, &depth), list) in self.color.iter_mut().zip(&self.depth).zip(fragments) {
+            if !list.is_empty() {
+                *color = oit::resolve_fragments(list, *color, depth, self.depth_range);
+            }
+        }
+    }
+
+    // box filter of the samples of every pixel into the color of a single sampled framebuffer
+    pub fn resolve(&self, target: &mut Framebuffer) {
+        assert!(
+            target.width == self.width && target.height == self.height,
+            "can only resolve into a framebuffer of the same size"
+        );
+        assert_eq!(
+            target.samples.count(),
+            1,
+            "can only resolve into a single sampled framebuffer"
+        );
+        let count = self.samples.count() as u32;
+        for (pixel, samples) in target
+            .color
+            .iter_mut()
+            .zip(self.color.chunks_exact(self.samples.count()))
+        {
+            let channel = |shift: u32| {
+                let sum: u32 = samples.iter().map(|s| (s >> shift) & 0xff).sum();
+                ((sum + count / 2) / count) << shift
+            };
+            *pixel = channel(24) | channel(16) | channel(8) | channel(0);
+        }
+    }
+
+    // splits the framebuffer into horizontal bands of `rows` rows each, to be drawn in parallel
+    pub fn bands_mut(&mut self, rows: usize) -> impl Iterator<Item = FramebufferBand<'_>> {
+        let band_len = self.width * rows * self.samples.count();
+        let (width, samples, depth_range) = (self.width, self.samples, self.depth_range);
+        let mut stencil = self
+            .stencil
+            .as_deref_mut()
+            .map(|stencil| stencil.chunks_mut(band_len));
+        let mut fragments = self
+            .fragments
+            .as_deref_mut()
+            .map(|fragments| fragments.chunks_mut(band_len));
+        self.color
+            .chunks_mut(band_len)
+            .zip(self.depth.chunks_mut(band_len))
+            .enumerate()
+            .map(move |(band, (color, depth))| FramebufferBand {
+                width,
+                first_row: band * rows,
+                samples,
+                depth_range,
+                color,
+                depth,
+                stencil: stencil.as_mut().and_then(|chunks| chunks.next()),
+                fragments: fragments.as_mut().and_then(|chunks| chunks.next()),
+            })
+    }
+
+    // the whole framebuffer as a single band
+    pub fn as_band_mut(&mut self) -> FramebufferBand<'_> {
+        let rows = self.height;
+        self.bands_mut(rows).next().unwrap()
+    }
+}
+
+// rows `first_row..first_row + rows()` of a framebuffer, what the per triangle raster functions
+// draw into, so threads can each own a part of the frame
+pub struct FramebufferBand<'a> {
+    pub width: usize,
+    pub first_row: usize,
+    pub samples: SamplePattern,
+    pub depth_range: DepthRange,
+    pub color: &'a mut [u32],
+    pub depth: &'a mut [f32],
+    pub stencil: Option<&'a mut [u8]>,
+    pub fragments: Option<&'a mut [Vec<Fragment>]>,
+}
+
+impl FramebufferBand<'_> {
+    pub fn rows(&self) -> usize {
+        self.color.len() / (self.width * self.samples.count())
+    }
+
+    // the stencil test of a draw and the face a triangle uses, None if there is nothing to test
+    pub fn stencil_state(
+        &self,
+        pipeline: &PipelineState,
+        front_facing: bool,
+    ) -> Option<(StencilState, StencilFace)> {
+        let state = pipeline.stencil.filter(|_| self.stencil.is_some())?;
+        Some((state, *state.face(front_facing)))
+    }
+
+    // true if the fragments of a draw go into the fragment lists rather than the color attachment
+    // those never write depth, the opaque depth only hides them
+    pub fn collects_fragments(&self, pipeline: &PipelineState) -> bool {
+        pipeline.order_independent && self.fragments.is_some()
+    }
+
+    // index of the first sample of a pixel given in framebuffer coordinates
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        debug_assert!(
+            x < self.width && (self.first_row..self.first_row + self.rows()).contains(&y)
+        );
+        coords_to_index(x, y - self.first_row, self.width) * self.samples.count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn access_outside_is_ignored() {
+        let mut framebuffer = Framebuffer::new(4, 3).with_stencil();
+        framebuffer.set_color(3, 2, 7);
+        framebuffer.set_color(4, 0, 9);
+        framebuffer.set_depth(0, 3, 0.5);
+        assert_eq!(framebuffer.color_at(3, 2), Some(7));
+        assert_eq!(framebuffer.color_at(4, 0), None);
+        assert_eq!(framebuffer.depth_at(0, 3), None);
+        assert_eq!(framebuffer.color().iter().filter(|&&c| c != 0).count(), 1);
+        assert!(framebuffer.depth().iter().all(|&z| z == f32::INFINITY));
+
+        framebuffer.stencil_mut().unwrap()[5] = 1;
+        framebuffer.set_depth(1, 1, 0.5);
+        framebuffer.clear(2);
+        assert!(framebuffer.color().iter().all(|&c| c == 2));
+        assert!(framebuffer.depth().iter().all(|&z| z == f32::INFINITY));
+        assert!(framebuffer.stencil().unwrap().iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn bands_cover_every_row_once() {
+        let mut framebuffer = Framebuffer::with_samples(5, 10, SamplePattern::standard(2));
+        let bands: Vec<(usize, usize)> = framebuffer
+            .bands_mut(4)
+            .map(|band| (band.first_row, band.rows()))
+            .collect();
+        assert_eq!(bands, [(0, 4), (4, 4), (8, 2)]);
+
+        for band in framebuffer.bands_mut(4) {
+            let first_row = band.first_row;
+            let id = band.index(1, first_row + 1);
+            band.color[id + 1] = 0xff;
+        }
+        // the second sample of (1, 1), (1, 5) and (1, 9)
+        for y in 0..10 {
+            let id = framebuffer.index(1, y).unwrap();
+            assert_eq!(framebuffer.color()[id + 1] == 0xff, y % 4 == 1);
+        }
+
+        let mut resolved = Framebuffer::new(5, 10);
+        framebuffer.resolve(&mut resolved);
+        assert_eq!(resolved.color_at(1, 5), Some(0x80));
+    }
+}
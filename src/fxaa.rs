@@ -0,0 +1,227 @@
+use crate::{argb_to_vec4, coords_to_index, vec4_to_argb, Framebuffer};
+
+// local contrast below max(EDGE_THRESHOLD_MIN, EDGE_THRESHOLD * brightest neighbour) is not an edge
+const EDGE_THRESHOLD: f32 = 0.125;
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+// how far along an edge to look for its ends, in pixels
+const SEARCH_STEPS: usize = 12;
+// how much of the sub pixel aliasing blend is applied, 0 turns it off
+const SUBPIXEL_QUALITY: f32 = 0.75;
+
+// perceived brightness, what the edge detection works on
+fn luma(color: u32) -> f32 {
+    let c = argb_to_vec4(color);
+    c.x * 0.299 + c.y * 0.587 + c.z * 0.114
+}
+
+// fast approximate anti-aliasing over a finished frame, after Timothy Lottes' FXAA 3.11
+// finds edges from luma contrast, estimates how far each pixel is from the end of its edge
+// and blends it with its neighbour across the edge by that amount
+// works on the color of a single sampled framebuffer, resolve multisampled ones first
+pub fn fxaa(framebuffer: &mut Framebuffer) {
+    assert_eq!(
+        framebuffer.samples().count(),
+        1,
+        "fxaa needs a resolved framebuffer"
+    );
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let source = framebuffer.color().to_vec();
+    let buffer = framebuffer.color_mut();
+    let lumas: Vec<f32> = source.iter().map(|&color| luma(color)).collect();
+    let luma_at = |x: isize, y: isize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        lumas[coords_to_index(x, y, width)]
+    };
+
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let center = luma_at(x, y);
+            let (north, south) = (luma_at(x, y - 1), luma_at(x, y + 1));
+            let (west, east) = (luma_at(x - 1, y), luma_at(x + 1, y));
+            let max = center.max(north).max(south).max(west).max(east);
+            let min = center.min(north).min(south).min(west).min(east);
+            let range = max - min;
+            if range < EDGE_THRESHOLD_MIN.max(max * EDGE_THRESHOLD) {
+                continue;
+            }
+
+            let (north_west, north_east) = (luma_at(x - 1, y - 1), luma_at(x + 1, y - 1));
+            let (south_west, south_east) = (luma_at(x - 1, y + 1), luma_at(x + 1, y + 1));
+
+            // an edge running along x changes luma mostly along y and the other way around
+            let horizontal = (north_west - 2.0 * west + south_west).abs()
+                + 2.0 * (north - 2.0 * center + south).abs()
+                + (north_east - 2.0 * east + south_east).abs();
+            let vertical = (north_west - 2.0 * north + north_east).abs()
+                + 2.0 * (west - 2.0 * center + east).abs()
+                + (south_west - 2.0 * south + south_east).abs();
+            let along_x = horizontal >= vertical;
+
+            // which side of the pixel the edge is on, the one with the steeper gradient
+            let (negative, positive) = if along_x {
+                (north, south)
+            } else {
+                (west, east)
+            };
+            let gradient_negative = (negative - center).abs();
+            let gradient_positive = (positive - center).abs();
+            let negative_side = gradient_negative >= gradient_positive;
+            let (side_luma, gradient) = if negative_side {
+                (negative, gradient_negative)
+            } else {
+                (positive, gradient_positive)
+            };
+            let step: isize = if negative_side { -1 } else { 1 };
+            let edge_luma = (center + side_luma) * 0.5;
+            let threshold = gradient * 0.25;
+
+            // walk along the edge in both directions until the luma between the two rows of pixels
+            // that form the edge stops looking like `edge_luma`
+            let sample_edge = |offset: isize| {
+                let (ax, ay, bx, by) = if along_x {
+                    (x + offset, y, x + offset, y + step)
+                } else {
+                    (x, y + offset, x + step, y + offset)
+                };
+                (luma_at(ax, ay) + luma_at(bx, by)) * 0.5 - edge_luma
+            };
+            let search = |direction: isize| {
+                for distance in 1..=SEARCH_STEPS as isize {
+                    let delta = sample_edge(direction * distance);
+                    if delta.abs() >= threshold {
+                        return (distance as f32, delta);
+                    }
+                }
+                (
+                    SEARCH_STEPS as f32,
+                    sample_edge(direction * SEARCH_STEPS as isize),
+                )
+            };
+            let (distance_negative, delta_negative) = search(-1);
+            let (distance_positive, delta_positive) = search(1);
+
+            // only the closer end matters, and only if the luma there moves away from the center
+            // the same way it does across the edge, otherwise this pixel is on the wrong side
+            let center_smaller = center - edge_luma < 0.0;
+            let (distance, delta) = if distance_negative < distance_positive {
+                (distance_negative, delta_negative)
+            } else {
+                (distance_positive, delta_positive)
+            };
+            let edge_length = distance_negative + distance_positive;
+            let edge_blend = if (delta < 0.0) != center_smaller {
+                0.5 - distance / edge_length
+            } else {
+                0.0
+            };
+
+            // isolated single pixel features that are not part of a longer edge
+            let average = (2.0 * (north + south + west + east)
+                + north_west
+                + north_east
+                + south_west
+                + south_east)
+                / 12.0;
+            let subpixel = ((average - center).abs() / range).clamp(0.0, 1.0);
+            let subpixel = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+            let subpixel_blend = subpixel * subpixel * SUBPIXEL_QUALITY;
+
+            let blend = edge_blend.max(subpixel_blend);
+            if blend <= 0.0 {
+                continue;
+            }
+            let (nx, ny) = if along_x {
+                (x, y + step)
+            } else {
+                (x + step, y)
+            };
+            let nx = nx.clamp(0, width as isize - 1) as usize;
+            let ny = ny.clamp(0, height as isize - 1) as usize;
+            let id = coords_to_index(x as usize, y as usize, width);
+            let neighbour = argb_to_vec4(source[coords_to_index(nx, ny, width)]);
+            buffer[id] = vec4_to_argb(argb_to_vec4(source[id]).lerp(neighbour, blend));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use glam::{UVec3, Vec2, Vec3, Vec4};
+
+    const SIZE: usize = 64;
+
+    // white quad rotated a bit, so all four edges are long and shallow staircases
+    fn rotated_quad() -> Mesh {
+        let rotation = glam::Mat2::from_angle(0.3);
+        let vertices = [(-0.6, -0.5), (0.6, -0.5), (-0.6, 0.5), (0.6, 0.5)].map(|(x, y)| {
+            let p = rotation * Vec2::new(x, y);
+            Vertex::new(
+                Vec4::new(p.x, p.y, 0.5, 1.0),
+                Vec3::Z,
+                Vec3::ONE,
+                Vec2::ZERO,
+            )
+        });
+        Mesh {
+            vertices: vertices.to_vec(),
+            triangle_indices: vec![UVec3::new(0, 1, 2), UVec3::new(1, 3, 2)],
+            ..Default::default()
+        }
+    }
+
+    fn render(pattern: SamplePattern) -> Framebuffer {
+        let mut target = Framebuffer::with_samples(SIZE, SIZE, pattern);
+        draw(
+            &rotated_quad(),
+            WHITE,
+            &PipelineState {
+                cull_mode: CullMode::None,
+                ..Default::default()
+            },
+            &mut target,
+        );
+        let mut resolved = Framebuffer::new(SIZE, SIZE);
+        target.resolve(&mut resolved);
+        resolved
+    }
+
+    fn error(buffer: &[u32], reference: &[u32]) -> f32 {
+        buffer
+            .iter()
+            .zip(reference)
+            .map(|(&a, &b)| ((a & 0xff) as f32 - (b & 0xff) as f32).abs() / 255.0)
+            .sum()
+    }
+
+    #[test]
+    fn smooths_the_edges_of_a_quad() {
+        let mut framebuffer = render(SamplePattern::single());
+        let aliased = framebuffer.color().to_vec();
+        fxaa(&mut framebuffer);
+        let smoothed = framebuffer.color();
+
+        // only pixels next to an edge change
+        for (id, (&before, &after)) in aliased.iter().zip(smoothed).enumerate() {
+            let (x, y) = index_to_coords(id, SIZE);
+            let near_edge = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|&(dx, dy)| {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                (0..SIZE as isize).contains(&nx)
+                    && (0..SIZE as isize).contains(&ny)
+                    && aliased[coords_to_index(nx as usize, ny as usize, SIZE)] != before
+            });
+            if !near_edge {
+                assert_eq!(before, after);
+            }
+        }
+
+        // and they move towards what 8x msaa sees
+        let reference = render(SamplePattern::standard(8));
+        let reference = reference.color();
+        let blended = smoothed.iter().filter(|&&c| c != 0 && c != WHITE).count();
+        assert!(blended > 100);
+        assert!(error(smoothed, reference) < error(&aliased, reference) * 0.6);
+    }
+}
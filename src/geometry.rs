@@ -1,4 +1,6 @@
+use crate::Material;
 use glam::{Mat4, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
+use std::collections::HashMap;
 use std::ops::{Add, Mul, MulAssign, Sub};
 
 #[derive(Debug, Copy, Clone)]
@@ -74,16 +76,24 @@ impl Mul<f32> for Vertex {
 impl MulAssign<f32> for Vertex {
     fn mul_assign(&mut self, rhs: f32) {
         self.pos *= rhs;
+        self.normal *= rhs;
         self.color *= rhs;
         self.uv *= rhs;
     }
 }
 
+crate::impl_varying!(Vertex {
+    pos,
+    normal,
+    color,
+    uv
+});
+
 #[derive(Debug, Copy, Clone)]
-pub struct Triangle {
-    pub v0: Vertex,
-    pub v1: Vertex,
-    pub v2: Vertex,
+pub struct Triangle<V = Vertex> {
+    pub v0: V,
+    pub v1: V,
+    pub v2: V,
 }
 
 pub enum VerticesOrder {
@@ -95,11 +105,24 @@ pub enum VerticesOrder {
     CBA,
 }
 
-impl Triangle {
-    pub fn new(v0: Vertex, v1: Vertex, v2: Vertex) -> Self {
+impl<V: Copy> Triangle<V> {
+    pub fn new(v0: V, v1: V, v2: V) -> Self {
         Self { v0, v1, v2 }
     }
 
+    pub fn reorder(&self, order: VerticesOrder) -> Self {
+        match order {
+            VerticesOrder::ABC => *self,
+            VerticesOrder::ACB => Self::new(self.v0, self.v2, self.v1),
+            VerticesOrder::BAC => Self::new(self.v1, self.v0, self.v2),
+            VerticesOrder::BCA => Self::new(self.v1, self.v2, self.v0),
+            VerticesOrder::CAB => Self::new(self.v2, self.v0, self.v1),
+            VerticesOrder::CBA => Self::new(self.v2, self.v1, self.v0),
+        }
+    }
+}
+
+impl Triangle {
     pub fn transform(&self, matrix: &Mat4) -> Self {
         let p0 = *matrix * self.v0.pos.xyz().extend(1.0);
         let p1 = *matrix * self.v1.pos.xyz().extend(1.0);
@@ -113,22 +136,13 @@ impl Triangle {
 
         result
     }
-
-    pub fn reorder(&self, order: VerticesOrder) -> Self {
-        match order {
-            VerticesOrder::ABC => *self,
-            VerticesOrder::ACB => Self::new(self.v0, self.v2, self.v1),
-            VerticesOrder::BAC => Self::new(self.v1, self.v0, self.v2),
-            VerticesOrder::BCA => Self::new(self.v1, self.v2, self.v0),
-            VerticesOrder::CAB => Self::new(self.v2, self.v0, self.v1),
-            VerticesOrder::CBA => Self::new(self.v2, self.v1, self.v0),
-        }
-    }
 }
 
+#[derive(Debug, Clone, Default)]
 pub struct Mesh {
     pub triangle_indices: Vec<UVec3>,
     pub vertices: Vec<Vertex>,
+    pub material: Material,
 }
 
 impl Mesh {
@@ -136,9 +150,18 @@ impl Mesh {
         Self {
             triangle_indices: Vec::new(),
             vertices: Vec::new(),
+            material: Material::default(),
         }
     }
 
+    // the vertices and triangles of `other` after the ones already there, the material stays
+    pub fn append(&mut self, other: &Mesh) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.triangle_indices
+            .extend(other.triangle_indices.iter().map(|&t| t + offset));
+    }
+
     pub fn add_section_from_buffers(
         &mut self,
         triangles: &[UVec3],
@@ -171,6 +194,12 @@ impl Mesh {
         // TODO: handle errors
         let mut result = Mesh::new();
         for primitive in mesh.primitives() {
+            // primitives are merged into one mesh, so a single double sided one disables culling for all
+            // and a single blended one blends all of them
+            let material = Material::from_gltf(&primitive.material());
+            result.material.double_sided |= material.double_sided;
+            result.material.blend |= material.blend;
+            result.material.order_independent |= material.order_independent;
             let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
             if let Some(indices_reader) = reader.read_indices() {
                 indices_reader.into_u32().for_each(|i| indices.push(i));
@@ -201,6 +230,110 @@ impl Mesh {
         result
     }
 
+    // wavefront obj positions, uvs, normals and polygonal faces, everything else is ignored
+    // faces are triangulated as fans, missing normals are smoothed from the faces around a vertex
+    pub fn load_from_obj(source: &str) -> Result<Mesh, String> {
+        let mut positions: Vec<Vec3> = Vec::new();
+        let mut uvs: Vec<Vec2> = Vec::new();
+        let mut normals: Vec<Vec3> = Vec::new();
+        // every distinct position/uv/normal combination becomes one vertex
+        let mut corners: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+        let mut smooth: Vec<bool> = Vec::new();
+        let mut result = Mesh::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let error = |message: &str| format!("line {}: {}", line_number + 1, message);
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            let floats = |tokens: std::str::SplitWhitespace| {
+                tokens
+                    .map(|t| t.parse::<f32>().map_err(|_| error("invalid number")))
+                    .collect::<Result<Vec<f32>, String>>()
+            };
+            match keyword {
+                "v" => match floats(tokens)?[..] {
+                    [x, y, z, ..] => positions.push(Vec3::new(x, y, z)),
+                    _ => return Err(error("a position needs 3 coordinates")),
+                },
+                "vt" => match floats(tokens)?[..] {
+                    [u, v, ..] => uvs.push(Vec2::new(u, v)),
+                    [u] => uvs.push(Vec2::new(u, 0.0)),
+                    _ => return Err(error("a texture coordinate needs at least 1 value")),
+                },
+                "vn" => match floats(tokens)?[..] {
+                    [x, y, z] => normals.push(Vec3::new(x, y, z)),
+                    _ => return Err(error("a normal needs 3 coordinates")),
+                },
+                "f" => {
+                    let mut face = Vec::new();
+                    for corner in tokens {
+                        // 1 based, negative indices count back from the last element read so far
+                        let index =
+                            |part: Option<&str>, len: usize| -> Result<Option<usize>, String> {
+                                match part.filter(|p| !p.is_empty()) {
+                                    None => Ok(None),
+                                    Some(part) => {
+                                        let i: i64 =
+                                            part.parse().map_err(|_| error("invalid index"))?;
+                                        let i = if i < 0 { len as i64 + i } else { i - 1 };
+                                        if (0..len as i64).contains(&i) {
+                                            Ok(Some(i as usize))
+                                        } else {
+                                            Err(error("index out of range"))
+                                        }
+                                    }
+                                }
+                            };
+                        let mut parts = corner.split('/');
+                        let position = index(parts.next(), positions.len())?
+                            .ok_or_else(|| error("a face corner needs a position"))?;
+                        let uv = index(parts.next(), uvs.len())?;
+                        let normal = index(parts.next(), normals.len())?;
+                        let id = *corners.entry((position, uv, normal)).or_insert_with(|| {
+                            result.vertices.push(Vertex::new(
+                                positions[position].extend(1.0),
+                                normal.map_or(Vec3::ZERO, |n| normals[n]),
+                                Vec3::ONE,
+                                uv.map_or(Vec2::ZERO, |uv| uvs[uv]),
+                            ));
+                            smooth.push(normal.is_none());
+                            result.vertices.len() as u32 - 1
+                        });
+                        face.push(id);
+                    }
+                    if face.len() < 3 {
+                        return Err(error("a face needs at least 3 corners"));
+                    }
+                    for i in 1..face.len() - 1 {
+                        result
+                            .triangle_indices
+                            .push(UVec3::new(face[0], face[i], face[i + 1]));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // area weighted, the cross product is twice the triangle area
+        for triangle in &result.triangle_indices {
+            let [a, b, c] = triangle
+                .to_array()
+                .map(|i| result.vertices[i as usize].pos.xyz());
+            let normal = (b - a).cross(c - a);
+            for i in triangle.to_array() {
+                if smooth[i as usize] {
+                    result.vertices[i as usize].normal += normal;
+                }
+            }
+        }
+        for (vertex, _) in result.vertices.iter_mut().zip(&smooth).filter(|(_, &s)| s) {
+            vertex.normal = vertex.normal.normalize_or_zero();
+        }
+        Ok(result)
+    }
+
     pub fn get_vertices_from_triangle_indices(&self, triangle_indices: UVec3) -> [Vertex; 3] {
         [
             self.vertices[triangle_indices.x as usize],
@@ -230,3 +363,48 @@ pub fn get_triangle_bounding_box_2d(positions: &[Vec2; 3]) -> BoundingBox2D {
         bottom,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{UVec3, Vec2, Vec3};
+
+    #[test]
+    fn obj_faces_are_triangulated() {
+        let source = "
+# a unit quad, the second corner repeats with a different uv
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 4/2/1
+f -4 -2 -1
+";
+        let mesh = Mesh::load_from_obj(source).unwrap();
+        assert_eq!(
+            mesh.triangle_indices,
+            [
+                UVec3::new(0, 1, 2),
+                UVec3::new(0, 2, 3),
+                UVec3::new(4, 5, 6)
+            ]
+        );
+        assert_eq!(mesh.vertices.len(), 7);
+        assert_eq!(mesh.vertices[3].uv, Vec2::new(1.0, 0.0));
+        assert_eq!(mesh.vertices[2].normal, Vec3::Z);
+        // no normals given, they come from the face
+        assert_eq!(mesh.vertices[4].pos, glam::Vec4::new(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(mesh.vertices[5].normal, Vec3::Z);
+    }
+
+    #[test]
+    fn obj_errors_name_the_line() {
+        let error = Mesh::load_from_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n").unwrap_err();
+        assert_eq!(error, "line 3: index out of range");
+        assert!(Mesh::load_from_obj("v 0 x 0").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use crate::{CompareFunction, ScreenTriangle, Tile, TILE_SIZE};
+use std::ops::RangeInclusive;
+
+// true if a fragment at `nearest` or further fails `compare` against anything up to `max`
+fn hidden(compare: CompareFunction, nearest: f32, max: f32) -> bool {
+    match compare {
+        CompareFunction::Less | CompareFunction::Greater => nearest >= max,
+        CompareFunction::LessEqual | CompareFunction::GreaterEqual => nearest > max,
+        _ => false,
+    }
+}
+
+// side of the square block of pixels summarized by one coarse depth value
+pub const HIZ_CELL_SIZE: usize = 8;
+const CELLS_PER_TILE: usize = TILE_SIZE / HIZ_CELL_SIZE;
+
+// coarse depth of a horizontal band of the z buffer, two levels deep:
+// bounds on the depth of every 8x8 cell, and the max depth of every tile
+//
+// a cell is read from the z buffer the first time a query needs it, after that it is only
+// updated from the triangles drawn over it, rescanning after every triangle costs more than it saves
+// `min` stays a lower bound, since it is lowered by every triangle touching the cell
+// `max` stays an upper bound, since depth writes only ever lower z, and it is tightened when
+// a triangle covers a whole cell: every pixel then either took the triangle depth or failed against
+// something closer, either way it is now at most the triangle max depth
+//
+// that only holds for compares that let depth move one way, with Greater and GreaterEqual
+// (reversed depth) depth is negated on the way in, so closer is still lower
+// any other compare makes the bounds useless, `is_usable` is false then
+pub struct HiZ {
+    width: usize,
+    first_row: usize,
+    rows: usize,
+    cells_x: usize,
+    cells_y: usize,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+    unread: Vec<bool>,
+    tile_max: Vec<f32>,
+    tile_dirty: Vec<bool>,
+    compare: CompareFunction,
+    sign: f32,
+}
+
+impl HiZ {
+    // nothing is known about the z buffer yet, no cell has been read
+    pub fn new(width: usize, first_row: usize, rows: usize, compare: CompareFunction) -> Self {
+        let cells_x = width.div_ceil(HIZ_CELL_SIZE);
+        let cells_y = rows.div_ceil(HIZ_CELL_SIZE);
+        let tiles_x = width.div_ceil(TILE_SIZE);
+        let tiles_y = rows.div_ceil(TILE_SIZE);
+        Self {
+            width,
+            first_row,
+            rows,
+            cells_x,
+            cells_y,
+            min: vec![f32::NEG_INFINITY; cells_x * cells_y],
+            max: vec![f32::INFINITY; cells_x * cells_y],
+            unread: vec![true; cells_x * cells_y],
+            tile_max: vec![f32::INFINITY; tiles_x * tiles_y],
+            tile_dirty: vec![true; tiles_x * tiles_y],
+            compare,
+            sign: match compare {
+                CompareFunction::Greater | CompareFunction::GreaterEqual => -1.0,
+                _ => 1.0,
+            },
+        }
+    }
+
+    pub fn is_usable(&self) -> bool {
+        matches!(
+            self.compare,
+            CompareFunction::Less
+                | CompareFunction::LessEqual
+                | CompareFunction::Greater
+                | CompareFunction::GreaterEqual
+        )
+    }
+
+    // depth range of the triangle as (nearest, farthest), in the order of the bounds
+    fn bounds<V>(&self, triangle: &ScreenTriangle<V>) -> (f32, f32) {
+        if self.sign < 0.0 {
+            (-triangle.max_depth, -triangle.min_depth)
+        } else {
+            (triangle.min_depth, triangle.max_depth)
+        }
+    }
+
+    // cell coordinates covered by a pixel rectangle of the band
+    fn cells(&self, rect: &Tile) -> (RangeInclusive<usize>, RangeInclusive<usize>) {
+        (
+            rect.left / HIZ_CELL_SIZE..=rect.right / HIZ_CELL_SIZE,
+            (rect.top - self.first_row) / HIZ_CELL_SIZE
+                ..=(rect.bottom - self.first_row) / HIZ_CELL_SIZE,
+        )
+    }
+
+    // pixel rectangle of a cell, in screen coordinates
+    fn cell_rect(&self, cell_x: usize, cell_y: usize) -> Tile {
+        let left = cell_x * HIZ_CELL_SIZE;
+        let top = cell_y * HIZ_CELL_SIZE;
+        Tile::new(
+            left,
+            self.first_row + top,
+            (left + HIZ_CELL_SIZE).min(self.width) - 1,
+            self.first_row + (top + HIZ_CELL_SIZE).min(self.rows) - 1,
+        )
+    }
+
+    fn read_cell(&mut self, cell_x: usize, cell_y: usize, z_buffer: &[f32]) {
+        let id = cell_x + cell_y * self.cells_x;
+        if !self.unread[id] {
+            return;
+        }
+        let rect = self.cell_rect(cell_x, cell_y);
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for y in rect.top - self.first_row..=rect.bottom - self.first_row {
+            let row = &z_buffer[y * self.width..];
+            for &z in &row[rect.left..=rect.right] {
+                min = min.min(z * self.sign);
+                max = max.max(z * self.sign);
+            }
+        }
+        self.min[id] = self.min[id].max(min);
+        self.max[id] = self.max[id].min(max);
+        self.unread[id] = false;
+    }
+
+    fn tile_id(&self, rect: &Tile) -> (usize, usize, usize) {
+        let tile_x = rect.left / TILE_SIZE;
+        let tile_y = (rect.top - self.first_row) / TILE_SIZE;
+        (
+            tile_x,
+            tile_y,
+            tile_x + tile_y * self.width.div_ceil(TILE_SIZE),
+        )
+    }
+
+    // true if the whole tile is already closer than the triangle
+    pub fn tile_occludes<V>(&mut self, tile: &Tile, triangle: &ScreenTriangle<V>) -> bool {
+        let nearest = self.bounds(triangle).0;
+        let (tile_x, tile_y, id) = self.tile_id(tile);
+        if self.tile_dirty[id] {
+            let mut max = f32::NEG_INFINITY;
+            let cells_y =
+                tile_y * CELLS_PER_TILE..((tile_y + 1) * CELLS_PER_TILE).min(self.cells_y);
+            let cells_x =
+                tile_x * CELLS_PER_TILE..((tile_x + 1) * CELLS_PER_TILE).min(self.cells_x);
+            for cell_y in cells_y {
+                for cell_x in cells_x.clone() {
+                    max = max.max(self.max[cell_x + cell_y * self.cells_x]);
+                }
+            }
+            self.tile_max[id] = max;
+            self.tile_dirty[id] = false;
+        }
+        hidden(self.compare, nearest, self.tile_max[id])
+    }
+
+    // true if every cell touched by `rect` is already closer than the triangle
+    pub fn rect_occludes<V>(
+        &mut self,
+        rect: &Tile,
+        triangle: &ScreenTriangle<V>,
+        z_buffer: &[f32],
+    ) -> bool {
+        let nearest = self.bounds(triangle).0;
+        let (cells_x, cells_y) = self.cells(rect);
+        for cell_y in cells_y {
+            for cell_x in cells_x.clone() {
+                let id = cell_x + cell_y * self.cells_x;
+                if hidden(self.compare, nearest, self.max[id]) {
+                    continue;
+                }
+                if !self.unread[id] {
+                    return false;
+                }
+                self.read_cell(cell_x, cell_y, z_buffer);
+                if !hidden(self.compare, nearest, self.max[id]) {
+                    return false;
+                }
+                let tile = self.tile_id(rect).2;
+                self.tile_dirty[tile] = true;
+            }
+        }
+        true
+    }
+
+    // `triangle` has just been drawn over `rect`, `complete` is false if the fragment shader
+    // discarded some of it, then only the min can be updated
+    pub fn update<V>(&mut self, rect: &Tile, triangle: &ScreenTriangle<V>, complete: bool) {
+        let (nearest, farthest) = self.bounds(triangle);
+        let (cells_x, cells_y) = self.cells(rect);
+        let mut changed = false;
+        for cell_y in cells_y {
+            for cell_x in cells_x.clone() {
+                let id = cell_x + cell_y * self.cells_x;
+                self.min[id] = self.min[id].min(nearest);
+                if !complete || farthest >= self.max[id] {
+                    continue;
+                }
+                // most triangles are smaller than a cell, those never get past the bounding box check
+                let cell = self.cell_rect(cell_x, cell_y);
+                if cell.left < rect.left
+                    || cell.right > rect.right
+                    || cell.top < rect.top
+                    || cell.bottom > rect.bottom
+                {
+                    continue;
+                }
+                // the triangle is convex, so it covers every pixel of the cell if it covers the corners
+                let corners = [
+                    (cell.left, cell.top),
+                    (cell.right, cell.top),
+                    (cell.left, cell.bottom),
+                    (cell.right, cell.bottom),
+                ];
+                let covered = corners.iter().all(|&(x, y)| {
+                    let e = triangle.edges.eval(crate::raster::pixel_center_fixed(x, y));
+                    triangle.edges.covers(e)
+                });
+                if covered {
+                    self.max[id] = farthest;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            let tile = self.tile_id(rect).2;
+            self.tile_dirty[tile] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{UVec3, Vec2, Vec3, Vec4};
+
+    // screen filling quad at depth `z`, split into a grid so it lands in every tile
+    fn wall(z: f32, cells: usize) -> Mesh {
+        let mut mesh = Mesh::new();
+        for j in 0..=cells {
+            for i in 0..=cells {
+                let p = glam::vec2(i as f32, j as f32) / cells as f32 * 2.0 - 1.0;
+                mesh.vertices.push(Vertex::new(
+                    Vec4::new(p.x, p.y, z, 1.0),
+                    Vec3::Z,
+                    Vec3::ONE,
+                    Vec2::ZERO,
+                ));
+            }
+        }
+        let id = |i: usize, j: usize| (i + j * (cells + 1)) as u32;
+        for j in 0..cells {
+            for i in 0..cells {
+                let (a, b, c, d) = (id(i, j), id(i + 1, j), id(i, j + 1), id(i + 1, j + 1));
+                mesh.triangle_indices.push(UVec3::new(a, b, c));
+                mesh.triangle_indices.push(UVec3::new(b, d, c));
+            }
+        }
+        mesh
+    }
+
+    fn draw(mesh: &Mesh, framebuffer: &mut Framebuffer) -> RasterStats {
+        let uniforms = DefaultUniforms::new(glam::Mat4::IDENTITY, glam::Mat4::IDENTITY, None);
+        raster_mesh(
+            mesh,
+            &DefaultShader,
+            &DefaultShader,
+            &uniforms,
+            &PipelineState {
+                cull_mode: CullMode::None,
+                ..Default::default()
+            },
+            &RenderSettings::default(),
+            framebuffer,
+        )
+    }
+
+    #[test]
+    fn hidden_triangles_are_culled() {
+        let mut framebuffer = Framebuffer::new(200, 150);
+        let near = draw(&wall(0.2, 4), &mut framebuffer);
+        assert_eq!(near.culled(), 0);
+
+        // everything behind the first wall is rejected without touching a pixel
+        let image = framebuffer.color().to_vec();
+        let far = draw(&wall(0.6, 16), &mut framebuffer);
+        assert!(far.triangles > 0);
+        assert_eq!(far.culled(), far.triangles);
+        assert_eq!(framebuffer.color(), image);
+
+        // and in front of it nothing is
+        let front = draw(&wall(0.1, 16), &mut framebuffer);
+        assert_eq!(front.culled(), 0);
+        assert!(framebuffer.depth().iter().all(|&z| (z - 0.1).abs() < 1e-6));
+    }
+
+    #[test]
+    fn reversed_depth_is_culled_the_other_way() {
+        let mut framebuffer = Framebuffer::new(200, 150).with_depth_range(DepthRange::Reversed);
+        draw(&wall(0.6, 4), &mut framebuffer);
+
+        let image = framebuffer.color().to_vec();
+        let far = draw(&wall(0.2, 16), &mut framebuffer);
+        assert_eq!(far.culled(), far.triangles);
+        assert_eq!(framebuffer.color(), image);
+
+        let front = draw(&wall(0.8, 16), &mut framebuffer);
+        assert_eq!(front.culled(), 0);
+        assert!(framebuffer.depth().iter().all(|&z| (z - 0.8).abs() < 1e-6));
+    }
+
+    #[test]
+    fn covered_cells_cull_later_triangles_of_the_same_draw() {
+        // the near wall is drawn first, its big triangles cover whole cells
+        let mut mesh = wall(0.2, 2);
+        let far = wall(0.6, 16);
+        let offset = mesh.vertices.len() as u32;
+        mesh.vertices.extend(far.vertices);
+        mesh.triangle_indices
+            .extend(far.triangle_indices.iter().map(|t| *t + offset));
+
+        let mut framebuffer = Framebuffer::new(200, 150);
+        let stats = draw(&mesh, &mut framebuffer);
+        assert!(stats.culled() > 0);
+
+        let mut near = Framebuffer::new(200, 150);
+        draw(&wall(0.2, 2), &mut near);
+        assert_eq!(framebuffer.color(), near.color());
+        assert_eq!(framebuffer.depth(), near.depth());
+    }
+
+    #[test]
+    fn small_triangles_use_the_cells() {
+        let mut framebuffer = Framebuffer::new(200, 150);
+        draw(&wall(0.2, 4), &mut framebuffer);
+        // a hole in the wall leaves one tile with a far max depth, the fine grid of the
+        // second wall still gets culled everywhere except around the hole
+        framebuffer.set_depth(100, 75, 1.0);
+        let far = draw(&wall(0.6, 40), &mut framebuffer);
+        assert!(far.cell_culled > 0);
+        assert!(far.culled() < far.triangles);
+    }
+}
@@ -1,176 +1,491 @@
 use std::path::Path;
 
-use glam::{Mat4, Vec2, Vec3, Vec4Swizzles};
+use glam::{Vec2, Vec4, Vec4Swizzles};
 
+pub mod blend;
 pub mod camera;
+pub mod clipping;
+pub mod debug_draw;
+pub mod export;
+pub mod framebuffer;
+pub mod fxaa;
 pub mod geometry;
+pub mod hiz;
+pub mod lines;
+pub mod material;
+pub mod msaa;
+pub mod oit;
+pub mod pipeline;
+pub mod raster;
+pub mod screen;
+pub mod settings;
+pub mod shader;
+pub mod stats;
+pub mod stencil;
+#[cfg(test)]
+pub mod test_utils;
 pub mod texture;
+pub mod tiles;
 pub mod transform;
 pub mod utils;
+pub mod varying;
+pub mod viewport;
 pub use {
-    camera::Camera,
+    blend::{BlendComponent, BlendFactor, BlendOperation, BlendState},
+    camera::{Camera, Camera2D, DepthRange},
+    clipping::*,
+    debug_draw::{DebugDraw, DebugLine},
+    export::{color_to_rgb, depth_to_gray, save_color, save_depth, ImageFormat},
+    framebuffer::{Framebuffer, FramebufferBand},
+    fxaa::fxaa,
     geometry::*,
+    hiz::{HiZ, HIZ_CELL_SIZE},
+    lines::{
+        clip_to_window, line_pixels, point_pixels, raster_clip_line, raster_line,
+        raster_mesh_outline, raster_point_sprite,
+    },
+    material::Material,
+    msaa::SamplePattern,
+    oit::Fragment,
+    pipeline::{
+        CompareFunction, CullMode, DepthBias, DepthState, FrontFace, PipelineState, PolygonMode,
+    },
+    raster::{EdgeFunction, TriangleEdges, LANES, LANE_OFFSETS, MAX_WINDOW_COORD, SUBPIXEL_BITS},
+    screen::{raster_layers_2d, raster_mesh_2d, ScreenShader, ScreenUniforms, ScreenVaryings},
+    settings::RenderSettings,
+    shader::{DefaultShader, DefaultUniforms, DefaultVaryings, FragmentShader, VertexShader},
+    stats::RasterStats,
+    stencil::{StencilFace, StencilOp, StencilState},
     texture::Texture,
+    tiles::{Tile, TileBins, TILE_SIZE},
     transform::{Transform, TransformInitialParams},
     utils::*,
+    varying::{ClipVertex, Varying, VaryingBlock},
+    viewport::Viewport,
 };
 
-pub fn raster_clipped_triangle(
-    triangle: &Triangle,
-    texture: Option<&Texture>,
-    buffer: &mut Vec<u32>,
-    z_buffer: &mut Vec<f32>,
-    viewport_size: Vec2,
-) {
+// a clipped triangle after perspective division and viewport mapping
+// everything the per pixel loop needs is computed once here
+pub struct ScreenTriangle<V> {
+    pub rec: [f32; 3],
+    pub ndc: [Vec4; 3],
+    pub pv: [V; 3],
+    pub sc: [Vec2; 3],
+    pub edges: TriangleEdges,
+    pub bb: BoundingBox2D,
+    // depth range of the vertices, every fragment of the triangle falls in it
+    pub min_depth: f32,
+    pub max_depth: f32,
+    // picks the stencil face
+    pub front_facing: bool,
+    // replaces the fragment shader output, used by the clip visualization
+    pub debug_color: Option<u32>,
+}
+
+impl<V> ScreenTriangle<V> {
+    // the pixels of `tile` the triangle bounding box overlaps
+    pub fn tile_rect(&self, tile: &Tile) -> Option<Tile> {
+        let bb = &self.bb;
+        let rect = Tile::new(
+            (bb.left as usize).max(tile.left),
+            (bb.top as usize).max(tile.top),
+            (bb.right as usize).min(tile.right),
+            (bb.bottom as usize).min(tile.bottom),
+        );
+        (rect.left <= rect.right && rect.top <= rect.bottom).then_some(rect)
+    }
+}
+
+// returns None if the triangle is backface culled or entirely outside the viewport and scissor
+pub fn setup_clipped_triangle<V: Varying>(
+    triangle: &Triangle<ClipVertex<V>>,
+    pipeline: &PipelineState,
+    framebuffer_size: Vec2,
+) -> Option<ScreenTriangle<V>> {
+    let bounds = pipeline.pixel_bounds(framebuffer_size)?;
+    let viewport = pipeline.viewport_on(framebuffer_size);
     let rec0 = 1.0 / triangle.v0.pos.w;
     let rec1 = 1.0 / triangle.v1.pos.w;
     let rec2 = 1.0 / triangle.v2.pos.w;
 
     // normalized device coordinates -> between -1 and 1
-    let ndc0 = triangle.v0.pos * rec0;
-    let ndc1 = triangle.v1.pos * rec1;
-    let ndc2 = triangle.v2.pos * rec2;
+    let mut ndc0 = triangle.v0.pos * rec0;
+    let mut ndc1 = triangle.v1.pos * rec1;
+    let mut ndc2 = triangle.v2.pos * rec2;
 
     // perspective division on all attributes
-    let pv0 = triangle.v0 * rec0;
-    let pv1 = triangle.v1 * rec1;
-    let pv2 = triangle.v2 * rec2;
+    let pv0 = triangle.v0.varyings.scale(rec0);
+    let pv1 = triangle.v1.varyings.scale(rec1);
+    let pv2 = triangle.v2.varyings.scale(rec2);
 
     // screen coordinates remapped to window
-    let sc0 = glam::vec2(
-        map_to_range(ndc0.x, -1.0, 1.0, 0.0, viewport_size.x),
-        map_to_range(ndc0.y, -1.0, 1.0, 0.0, viewport_size.y),
-    );
-    let sc1 = glam::vec2(
-        map_to_range(ndc1.x, -1.0, 1.0, 0.0, viewport_size.x),
-        map_to_range(ndc1.y, -1.0, 1.0, 0.0, viewport_size.y),
-    );
-    let sc2 = glam::vec2(
-        map_to_range(ndc2.x, -1.0, 1.0, 0.0, viewport_size.x),
-        map_to_range(ndc2.y, -1.0, 1.0, 0.0, viewport_size.y),
-    );
+    let sc0 = viewport.to_window(ndc0.xy());
+    let sc1 = viewport.to_window(ndc1.xy());
+    let sc2 = viewport.to_window(ndc2.xy());
+
+    // winding is decided on the snapped positions, so it always agrees with what gets rasterized
+    let edges = TriangleEdges::new(&[sc0, sc1, sc2]);
+    let area = edges.signed_area as f32;
+    if cull_triangle_backface(area, pipeline.cull_mode, pipeline.front_face) {
+        return None;
+    }
+
+    // the bias moves the whole depth plane, so it is baked into the vertices
+    let bias = pipeline
+        .depth
+        .bias
+        .offset(&[sc0, sc1, sc2], [ndc0.z, ndc1.z, ndc2.z]);
+    ndc0.z += bias;
+    ndc1.z += bias;
+    ndc2.z += bias;
 
-    let triangle_area = edge_function(sc0, sc1, sc2);
     // bb - bounding box of the triangle
-    if let Some(bb) = triangle_screen_bounding_box(&[sc0, sc1, sc2], viewport_size) {
-        for y in (bb.top as usize)..=bb.bottom as usize {
-            for x in (bb.left as usize)..=bb.right as usize {
+    triangle_screen_bounding_box(&[sc0, sc1, sc2], &bounds).map(|bb| ScreenTriangle {
+        rec: [rec0, rec1, rec2],
+        ndc: [ndc0, ndc1, ndc2],
+        pv: [pv0, pv1, pv2],
+        sc: [sc0, sc1, sc2],
+        edges,
+        bb,
+        min_depth: ndc0.z.min(ndc1.z).min(ndc2.z),
+        max_depth: ndc0.z.max(ndc1.z).max(ndc2.z),
+        front_facing: is_front_facing(area, pipeline.front_face),
+        debug_color: None,
+    })
+}
+
+// sets up every triangle of the clipped polygon, tinting them if clip visualization is on
+pub fn setup_clip_result<V: Varying>(
+    clip_result: &ClipResult<V>,
+    pipeline: &PipelineState,
+    settings: &RenderSettings,
+    framebuffer_size: Vec2,
+    mut emit: impl FnMut(ScreenTriangle<V>),
+) {
+    let tint = settings.clip_visualization && clip_result.is_clipped();
+    let mut fan_index = 0;
+    clip_result.for_each_triangle(|tri| {
+        if let Some(mut screen_triangle) = setup_clipped_triangle(tri, pipeline, framebuffer_size) {
+            if tint {
+                screen_triangle.debug_color = Some(clip_debug_color(fan_index));
+            }
+            emit(screen_triangle);
+        }
+        fan_index += 1;
+    });
+}
+
+// rasterizes the part of the triangle that falls inside `tile` into a band of a framebuffer
+// returns false if the fragment shader discarded any fragment that passed the depth test
+pub fn raster_screen_triangle<U, V: Varying, FS: FragmentShader<U, V>>(
+    triangle: &ScreenTriangle<V>,
+    tile: &Tile,
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    target: &mut FramebufferBand,
+) -> bool {
+    if target.samples.count() > 1 {
+        return msaa::raster_screen_triangle_msaa(
+            triangle,
+            tile,
+            fragment_shader,
+            uniforms,
+            pipeline,
+            target,
+        );
+    }
+    let [rec0, rec1, rec2] = triangle.rec;
+    let [ndc0, ndc1, ndc2] = triangle.ndc;
+    let pv = &triangle.pv;
+    let Some(Tile {
+        left,
+        top,
+        right,
+        bottom,
+    }) = triangle.tile_rect(tile)
+    else {
+        return true;
+    };
+
+    // the edge functions are linear, so they are evaluated once at the top left pixel
+    // and then stepped by a constant per pixel and per row
+    let edges = &triangle.edges;
+    let step_x = edges.step_x();
+    let step_y = edges.step_y();
+    let mut e_row = edges.eval(raster::pixel_center_fixed(left, top));
+    // barycentric change from one lane of a block to the next
+    let lane_step = step_x.map(|step| LANE_OFFSETS * (step as f32 * edges.inv_area));
+    let stencil = target.stencil_state(pipeline, triangle.front_facing);
+    let collect = target.collects_fragments(pipeline);
+    let depth_compare = target.depth_range.depth_compare(pipeline.depth.compare);
+    let mut discarded = false;
+
+    for y in top..=bottom {
+        let span = edges.row_span(e_row, right - left + 1);
+        let row = e_row;
+        e_row = [0, 1, 2].map(|i| e_row[i] + step_y[i]);
+        // whole row outside of the triangle
+        let Some((first, last)) = span else {
+            continue;
+        };
+
+        // the span is exact, every pixel in it passes the fill rule
+        // it is walked in blocks of LANES pixels, each lane of the glam vectors is one pixel
+        // blocks are aligned to the screen rather than to the span, so a pixel ends up in the same lane
+        // and gets the exact same float math no matter which tile or triangle it is drawn by
+        let (span_start, span_end) = (left + first, left + last);
+        let mut x = span_start & !(LANES - 1);
+        let mut e = [0, 1, 2].map(|i| row[i] + step_x[i] * (x as i64 - left as i64));
+        while x <= span_end {
+            // lanes of the block inside the span, as a bitmask like the depth test result
+            let alive = (!0u32 << span_start.saturating_sub(x))
+                & (!0u32 >> (31 - (span_end - x).min(LANES - 1)));
+            let [b0, b1, b2] =
+                [0, 1, 2].map(|i| Vec4::splat(e[i] as f32 * edges.inv_area) + lane_step[i]);
+            e = [0, 1, 2].map(|i| e[i] + step_x[i] * LANES as i64);
+
+            let correction = (b0 * rec0 + b1 * rec1 + b2 * rec2).recip();
+            let depth = b0 * ndc0.z + b1 * ndc1.z + b2 * ndc2.z;
+            let pixel_id = target.index(x, y);
+            // lanes outside of the span still read their pixel, the depth test of those is masked out
+            // only a block at the end of a row whose width isn't a multiple of LANES has to go lane by lane
+            let old_depth = if x + LANES <= target.width {
+                Vec4::from_slice(&target.depth[pixel_id..pixel_id + LANES])
+            } else {
+                Vec4::from_array(std::array::from_fn(|k| {
+                    target.depth.get(pixel_id + k).copied().unwrap_or(0.0)
+                }))
+            };
+            let mut passed = depth_compare.test_lanes(depth, old_depth).bitmask() & alive;
+            if let (Some((state, face)), Some(stencil)) = (&stencil, target.stencil.as_deref_mut())
+            {
+                for k in (0..LANES).filter(|&k| alive & (1 << k) != 0) {
+                    let depth_passed = passed & (1 << k) != 0;
+                    if !state.test_and_update(face, depth_passed, &mut stencil[pixel_id + k]) {
+                        passed &= !(1 << k);
+                    }
+                }
+            }
+            if passed != 0 {
+                // perspective correct weights of the three vertices
+                let w0 = b0 * correction;
+                let w1 = b1 * correction;
+                let w2 = b2 * correction;
+                // dead lanes get the varyings extrapolated from the triangle too, so neighbouring
+                // lanes always give the shader screen space derivatives
+                let varyings = VaryingBlock::new(pv, [w0, w1, w2]);
                 // +0.5 to take the center of the pixel
-                let coords = glam::vec2(x as f32, y as f32) + 0.5;
-                let pixel_id = coords_to_index(x, y, viewport_size.x as usize);
-                if let Some(bary) = barycentric_coords(coords, sc0, sc1, sc2, triangle_area) {
-                    let correction = bary.x * rec0 + bary.y * rec1 + bary.z * rec2;
-                    let correction = 1.0 / correction;
-                    let depth = bary.x * ndc0.z + bary.y * ndc1.z + bary.z * ndc2.z;
-                    if depth < z_buffer[pixel_id] {
-                        z_buffer[pixel_id] = depth;
-                        let normal =
-                            bary.x * pv0.normal + bary.y * pv1.normal + bary.z * pv2.normal;
-                        let normal = normal * correction;
-                        let n_dot_l = normal.dot(Vec3::ONE.normalize()); // light in the position (1, 1, 1)
-                        let ambient = glam::vec3(0.3, 0.3, 0.3); // fixed amount of ambient light
-                        let color = bary.x * pv0.color + bary.y * pv1.color + bary.z * pv2.color;
-                        let color = color * correction * n_dot_l + ambient;
-                        match texture {
-                            Some(texture) => {
-                                let tex_coords =
-                                    bary.x * pv0.uv + bary.y * pv1.uv + bary.z * pv2.uv;
-                                let tex_coords = tex_coords * correction;
-                                let tex_color = texture.rgb_at_uv(tex_coords.x, tex_coords.y);
-                                let r = (tex_color >> 16) as u8;
-                                let g = (tex_color >> 8) as u8;
-                                let b = tex_color as u8;
-                                buffer[pixel_id] = from_u8_rgb(
-                                    (r as f32 * color.x) as u8,
-                                    (g as f32 * color.y) as u8,
-                                    (b as f32 * color.z) as u8,
-                                );
+                let frag_x = Vec4::splat(x as f32 + 0.5) + LANE_OFFSETS;
+                let inv_w = correction.recip();
+                let frag_coords: [Vec4; LANES] = std::array::from_fn(|k| {
+                    Vec4::new(frag_x[k], y as f32 + 0.5, depth[k], inv_w[k])
+                });
+                let colors =
+                    fragment_shader.fragment_block(&frag_coords, &varyings, passed, uniforms);
+                for (k, color) in colors.into_iter().enumerate() {
+                    if passed & (1 << k) == 0 {
+                        continue;
+                    }
+                    match color {
+                        Some(color) => {
+                            if let (true, Some(fragments)) =
+                                (collect, target.fragments.as_deref_mut())
+                            {
+                                fragments[pixel_id + k].push(Fragment {
+                                    depth: depth[k],
+                                    color: triangle.debug_color.unwrap_or(color),
+                                });
+                            } else {
+                                if pipeline.depth.write {
+                                    target.depth[pixel_id + k] = depth[k];
+                                }
+                                let dst = target.color[pixel_id + k];
+                                target.color[pixel_id + k] = triangle
+                                    .debug_color
+                                    .unwrap_or_else(|| pipeline.blend_color(color, dst));
                             }
-                            None => {
-                                buffer[pixel_id] = from_u8_rgb(
-                                    (color.x * 255.0) as u8,
-                                    (color.y * 255.0) as u8,
-                                    (color.z * 255.0) as u8,
-                                );
+                            if let (Some((state, face)), Some(stencil)) =
+                                (&stencil, target.stencil.as_deref_mut())
+                            {
+                                state.apply(face.pass_op, &mut stencil[pixel_id + k]);
                             }
                         }
+                        None => discarded = true,
                     }
                 }
             }
+            x += LANES;
         }
     }
+    !discarded
 }
 
-pub fn raster_triangle(
-    vertices: &[Vertex; 3],
-    mvp: &Mat4,
-    model_matrix: &Mat4,
-    texture: Option<&Texture>,
-    buffer: &mut Vec<u32>,
-    z_buffer: &mut Vec<f32>,
-    viewport_size: Vec2,
+pub fn raster_clipped_triangle<U, V: Varying, FS: FragmentShader<U, V>>(
+    triangle: &Triangle<ClipVertex<V>>,
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
 ) {
-    let trans_inv = glam::Mat4::transpose(&glam::Mat4::inverse(model_matrix));
+    if let Some(screen_triangle) = setup_clipped_triangle(triangle, pipeline, framebuffer.size()) {
+        let viewport = Tile::new(0, 0, framebuffer.width() - 1, framebuffer.height() - 1);
+        raster_screen_triangle(
+            &screen_triangle,
+            &viewport,
+            fragment_shader,
+            uniforms,
+            pipeline,
+            &mut framebuffer.as_band_mut(),
+        );
+    }
+}
 
-    let triangle = Triangle {
-        v0: vertices[0],
-        v1: vertices[1],
-        v2: vertices[2],
-    };
-    let mut clip_tri = triangle.transform(mvp);
-    clip_tri.v0.normal = (trans_inv * clip_tri.v0.normal.extend(0.0)).xyz();
-    clip_tri.v1.normal = (trans_inv * clip_tri.v1.normal.extend(0.0)).xyz();
-    clip_tri.v2.normal = (trans_inv * clip_tri.v2.normal.extend(0.0)).xyz();
-
-    match clip_cull_triangle(&clip_tri) {
-        ClipResult::None => {}
-        ClipResult::One(tri) => {
-            raster_clipped_triangle(&tri, texture, buffer, z_buffer, viewport_size);
-        }
-        ClipResult::Two(tri) => {
-            raster_clipped_triangle(&tri.0, texture, buffer, z_buffer, viewport_size);
-            raster_clipped_triangle(&tri.1, texture, buffer, z_buffer, viewport_size);
-        }
+// single threaded path, rasterizes straight into the framebuffer
+pub fn raster_triangle<U, VS, FS>(
+    vertices: &[Vertex; 3],
+    vertex_shader: &VS,
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    settings: &RenderSettings,
+    framebuffer: &mut Framebuffer,
+) where
+    VS: VertexShader<U>,
+    FS: FragmentShader<U, VS::Varyings>,
+{
+    let clip_tri = Triangle::new(
+        vertex_shader.vertex(&vertices[0], uniforms),
+        vertex_shader.vertex(&vertices[1], uniforms),
+        vertex_shader.vertex(&vertices[2], uniforms),
+    );
+
+    let framebuffer_size = framebuffer.size();
+    let viewport = Tile::new(0, 0, framebuffer.width() - 1, framebuffer.height() - 1);
+    let guard_band = settings.guard_band_for(&pipeline.viewport_on(framebuffer_size));
+    let mut target = framebuffer.as_band_mut();
+    let clip_result = clip_cull_triangle(&clip_tri, guard_band);
+    setup_clip_result(
+        &clip_result,
+        pipeline,
+        settings,
+        framebuffer_size,
+        |screen_triangle| {
+            raster_screen_triangle(
+                &screen_triangle,
+                &viewport,
+                fragment_shader,
+                uniforms,
+                pipeline,
+                &mut target,
+            );
+        },
+    );
+}
+
+// binning rasterizer: all vertices are shaded once, triangles are clipped and set up,
+// then binned into screen tiles which are shaded in parallel
+// produces exactly the same image as calling `raster_triangle` for every triangle in order
+// multisampled framebuffers get every sample drawn, resolve them afterwards to present them
+// returns how many triangles the hierarchical z test skipped
+// meshes drawn as lines or points take the single threaded path in `lines` instead
+pub fn raster_mesh<U, VS, FS>(
+    mesh: &Mesh,
+    vertex_shader: &VS,
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    settings: &RenderSettings,
+    framebuffer: &mut Framebuffer,
+) -> RasterStats
+where
+    U: Sync,
+    VS: VertexShader<U>,
+    VS::Varyings: Sync,
+    FS: FragmentShader<U, VS::Varyings> + Sync,
+{
+    if pipeline.polygon_mode != PolygonMode::Fill {
+        return raster_mesh_outline(
+            mesh,
+            vertex_shader,
+            fragment_shader,
+            uniforms,
+            pipeline,
+            settings,
+            framebuffer,
+        );
     }
+    let screen_triangles = setup_mesh(
+        mesh,
+        vertex_shader,
+        uniforms,
+        pipeline,
+        settings,
+        framebuffer.size(),
+    );
+    let mut bins = TileBins::new(framebuffer.width(), framebuffer.height());
+    bins.bin(&screen_triangles);
+    tiles::raster_tiles(
+        &bins,
+        &screen_triangles,
+        fragment_shader,
+        uniforms,
+        &pipeline.for_material(&mesh.material),
+        framebuffer,
+    )
 }
 
-pub fn raster_mesh(
+// shades every vertex once, then clips and sets up all the triangles of the mesh
+pub fn setup_mesh<U, VS: VertexShader<U>>(
     mesh: &Mesh,
-    model_matrix: &Mat4,
-    mvp: &Mat4,
-    texture: Option<&Texture>,
-    buffer: &mut Vec<u32>,
-    z_buffer: &mut Vec<f32>,
-    viewport_size: Vec2,
-) {
+    vertex_shader: &VS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    settings: &RenderSettings,
+    framebuffer_size: Vec2,
+) -> Vec<ScreenTriangle<VS::Varyings>> {
+    let pipeline = &pipeline.for_material(&mesh.material);
+    let clip_vertices: Vec<ClipVertex<VS::Varyings>> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| vertex_shader.vertex(vertex, uniforms))
+        .collect();
+
+    let guard_band = settings.guard_band_for(&pipeline.viewport_on(framebuffer_size));
+    let mut screen_triangles = Vec::with_capacity(mesh.triangle_indices.len());
     for triangle_indices in &mesh.triangle_indices {
-        let vertices = mesh.get_vertices_from_triangle_indices(*triangle_indices);
-        raster_triangle(
-            &vertices,
-            model_matrix,
-            mvp,
-            texture,
-            buffer,
-            z_buffer,
-            viewport_size,
+        let clip_tri = Triangle::new(
+            clip_vertices[triangle_indices.x as usize],
+            clip_vertices[triangle_indices.y as usize],
+            clip_vertices[triangle_indices.z as usize],
+        );
+        let clip_result = clip_cull_triangle(&clip_tri, guard_band);
+        setup_clip_result(
+            &clip_result,
+            pipeline,
+            settings,
+            framebuffer_size,
+            |screen_triangle| screen_triangles.push(screen_triangle),
         );
     }
+
+    screen_triangles
 }
 
-pub fn triangle_screen_bounding_box(
-    poss: &[Vec2; 3],
-    viewport_size: Vec2,
-) -> Option<BoundingBox2D> {
+// the bounding box of the triangle cut to `bounds`, None if they don't overlap
+pub fn triangle_screen_bounding_box(poss: &[Vec2; 3], bounds: &Tile) -> Option<BoundingBox2D> {
     let bb = get_triangle_bounding_box_2d(poss);
+    let (min_x, max_x) = (bounds.left as f32, bounds.right as f32);
+    let (min_y, max_y) = (bounds.top as f32, bounds.bottom as f32);
 
-    if bb.left >= viewport_size.x || bb.right < 0.0 || bb.bottom >= viewport_size.y || bb.top < 0.0
-    {
+    // top is the smallest y, bottom the largest
+    if bb.left >= max_x + 1.0 || bb.right < min_x || bb.top >= max_y + 1.0 || bb.bottom < min_y {
         None
     } else {
-        let left = bb.left.max(0.0);
-        let right = bb.right.min(viewport_size.x - 1.0);
-        let bottom = bb.bottom.max(0.0);
-        let top = bb.top.min(viewport_size.y - 1.0);
+        let left = bb.left.max(min_x);
+        let right = bb.right.min(max_x);
+        let top = bb.top.max(min_y);
+        let bottom = bb.bottom.min(max_y);
 
         Some(BoundingBox2D {
             left,
@@ -181,151 +496,14 @@ pub fn triangle_screen_bounding_box(
     }
 }
 
-pub enum ClipResult {
-    None,
-    One(Triangle),
-    Two((Triangle, Triangle)),
-}
-
-//View Frustum Culling
-pub fn cull_triangle_view_frustum(triangle: &Triangle) -> bool {
-    // cull tests against the 6 planes
-    if triangle.v0.pos.x > triangle.v0.pos.w
-        && triangle.v1.pos.x > triangle.v1.pos.w
-        && triangle.v2.pos.x > triangle.v2.pos.w
-    {
-        return true;
-    }
-    if triangle.v0.pos.x < -triangle.v0.pos.w
-        && triangle.v1.pos.x < -triangle.v1.pos.w
-        && triangle.v2.pos.x < -triangle.v2.pos.w
-    {
-        return true;
-    }
-    if triangle.v0.pos.y > triangle.v0.pos.w
-        && triangle.v1.pos.y > triangle.v1.pos.w
-        && triangle.v2.pos.y > triangle.v2.pos.w
-    {
-        return true;
-    }
-    if triangle.v0.pos.y < -triangle.v0.pos.w
-        && triangle.v1.pos.y < -triangle.v1.pos.w
-        && triangle.v2.pos.y < -triangle.v2.pos.w
-    {
-        return true;
-    }
-    if triangle.v0.pos.z > triangle.v0.pos.w
-        && triangle.v1.pos.z > triangle.v1.pos.w
-        && triangle.v2.pos.z > triangle.v2.pos.w
-    {
-        return true;
-    }
-    if triangle.v0.pos.z < 0.0 && triangle.v1.pos.z < 0.0 && triangle.v2.pos.z < 0.0 {
-        return true;
-    }
-
-    false
-}
-
-pub fn clip_triangle_two(triangle: &Triangle) -> (Triangle, Triangle) {
-    // calculate alpha values for getting adjusted vertices
-    let alpha_a = (-triangle.v0.pos.z) / (triangle.v1.pos.z - triangle.v0.pos.z);
-    let alpha_b = (-triangle.v0.pos.z) / (triangle.v2.pos.z - triangle.v0.pos.z);
-
-    // interpolate to get v0a and v0b
-    let v0_a = lerp(triangle.v0, triangle.v1, alpha_a);
-    let v0_b = lerp(triangle.v0, triangle.v2, alpha_b);
-
-    // draw triangles
-    let mut result_a = *triangle;
-    let mut result_b = *triangle;
-
-    result_a.v0 = v0_a;
-
-    result_b.v0 = v0_a;
-    result_b.v1 = v0_b;
-
-    let green = Vec3::new(0.0, 1.0, 0.0);
-    let blue = Vec3::new(0.0, 0.0, 1.0);
-
-    result_a.v0.color = green;
-    result_a.v1.color = green;
-    result_a.v2.color = green;
-    result_b.v0.color = blue;
-    result_b.v1.color = blue;
-    result_b.v2.color = blue;
-
-    (result_a, result_b)
-}
-
-pub fn clip_triangle_one(triangle: &Triangle) -> Triangle {
-    // calculate alpha values for getting adjusted vertices
-    let alpha_a = (-triangle.v0.pos.z) / (triangle.v2.pos.z - triangle.v0.pos.z);
-    let alpha_b = (-triangle.v1.pos.z) / (triangle.v2.pos.z - triangle.v1.pos.z);
-
-    // interpolate to get v0a and v0b
-    let mut v0 = lerp(triangle.v0, triangle.v2, alpha_a);
-    let mut v1 = lerp(triangle.v1, triangle.v2, alpha_b);
-
-    let mut v2 = triangle.v2;
-
-    let red = Vec3::new(1.0, 0.0, 0.0);
-
-    v0.color = red;
-    v1.color = red;
-    v2.color = red;
-
-    // draw triangles
-    Triangle { v0, v1, v2 }
-}
-
-pub fn clip_cull_triangle(triangle: &Triangle) -> ClipResult {
-    /*if cull_triangle_backface(triangle) {
-        ClipResult::None
-    } else*/
-    if cull_triangle_view_frustum(triangle) {
-        ClipResult::None
-    } else {
-        // clipping routines
-        if triangle.v0.pos.z < 0.0 {
-            if triangle.v1.pos.z < 0.0 {
-                ClipResult::One(clip_triangle_one(triangle))
-            } else if triangle.v2.pos.z < 0.0 {
-                ClipResult::One(clip_triangle_one(&triangle.reorder(VerticesOrder::ACB)))
-            } else {
-                ClipResult::Two(clip_triangle_two(&triangle.reorder(VerticesOrder::ACB)))
-            }
-        } else if triangle.v1.pos.z < 0.0 {
-            if triangle.v2.pos.z < 0.0 {
-                ClipResult::One(clip_triangle_one(&triangle.reorder(VerticesOrder::BCA)))
-            } else {
-                ClipResult::Two(clip_triangle_two(&triangle.reorder(VerticesOrder::BAC)))
-            }
-        } else if triangle.v2.pos.z < 0.0 {
-            ClipResult::Two(clip_triangle_two(&triangle.reorder(VerticesOrder::CBA)))
-        } else {
-            // no near clipping necessary
-            //return original
-            ClipResult::One(*triangle)
-        }
-    }
-}
-
-// don't fully understand this function
-// but I know it's checking to see if the triangle is (somewhat) facing the camera
-pub fn cull_triangle_backface(triangle: &Triangle) -> bool {
-    let normal = (triangle.v1.pos.xyz() - triangle.v1.pos.w)
-        .cross(triangle.v2.pos.xyz() - triangle.v0.pos.xyz()); // should be divided by w?
-                                                               // any is vertex valid
-    let view_dir = -Vec3::Z;
-    // also we don't care about normalizing
-    // if negative facing the camera
-    normal.dot(view_dir) >= 0.0
+pub fn load_gltf(path: &Path) -> Mesh {
+    load_gltf_textured(path).unwrap().0
 }
 
-pub fn load_gltf(path: &Path) -> Mesh {
-    // handle loading textures, cameras, meshes here
-    let (document, buffers, _images) = gltf::import(path).unwrap();
+// the first mesh of the scene and the base color texture of its first primitive, if it has one
+pub fn load_gltf_textured(path: &Path) -> Result<(Mesh, Option<Texture>), gltf::Error> {
+    // handle loading cameras here
+    let (document, buffers, images) = gltf::import(path)?;
 
     for scene in document.scenes() {
         for node in scene.nodes() {
@@ -345,88 +523,38 @@ pub fn load_gltf(path: &Path) -> Mesh {
                 node.transform().decomposed().2,
             );
             if let Some(mesh) = node.mesh() {
-                return Mesh::load_from_gltf(&mesh, &buffers);
+                let texture = mesh
+                    .primitives()
+                    .next()
+                    .and_then(|primitive| {
+                        primitive
+                            .material()
+                            .pbr_metallic_roughness()
+                            .base_color_texture()
+                    })
+                    .and_then(|info| {
+                        let image = &images[info.texture().source().index()];
+                        let depth = match image.format {
+                            gltf::image::Format::R8G8B8 => 3,
+                            gltf::image::Format::R8G8B8A8 => 4,
+                            _ => return None,
+                        };
+                        Some(Texture::from_pixels(
+                            image.width as usize,
+                            image.height as usize,
+                            depth,
+                            &image.pixels,
+                        ))
+                    });
+                return Ok((Mesh::load_from_gltf(&mesh, &buffers), texture));
             }
         }
     }
 
-    Mesh::new()
+    Ok((Mesh::new(), None))
 }
 
-pub fn raster_triangle_2d(
-    triangle: &Triangle,
-    texture: Option<&Texture>,
-    buffer: &mut Vec<u32>,
-    z_buffer: &mut Vec<f32>,
-    viewport_size: Vec2,
-) {
-    let v0 = triangle.v0;
-    let v1 = triangle.v1;
-    let v2 = triangle.v2;
-
-    let triangle_area = edge_function(v1.pos.xy(), v2.pos.xy(), v0.pos.xy());
-    // bb - bounding box of the triangle
-    if let Some(bb) =
-        triangle_screen_bounding_box(&[v0.pos.xy(), v1.pos.xy(), v2.pos.xy()], viewport_size)
-    {
-        for y in (bb.top as usize)..=bb.bottom as usize {
-            for x in (bb.left as usize)..=bb.right as usize {
-                // +0.5 to take the center of the pixel
-                let coords = glam::vec2(x as f32, y as f32) + 0.5;
-                let pixel_id = coords_to_index(x, y, viewport_size.x as usize);
-                if let Some(bary) =
-                    barycentric_coords(coords, v0.pos.xy(), v1.pos.xy(), v2.pos.xy(), triangle_area)
-                {
-                    let color = bary.x * v0.color + bary.y * v1.color + bary.z * v2.color;
-                    let depth = bary.x * v0.pos.z + bary.y * v1.pos.z + bary.z * v2.pos.z;
-                    if depth <= z_buffer[pixel_id] {
-                        z_buffer[pixel_id] = depth;
-                        match &texture {
-                            Some(texture) => {
-                                let tex_coords = bary.x * v0.uv + bary.y * v1.uv + bary.z * v2.uv;
-                                let tex_color = texture.rgb_at_uv(tex_coords.x, tex_coords.y);
-                                let alpha = (tex_color >> 24) as u8;
-                                let red = (tex_color >> 16) as u8;
-                                let green = (tex_color >> 8) as u8;
-                                let blue = tex_color as u8;
-                                if alpha != 0 {
-                                    buffer[pixel_id] = from_u8_argb(
-                                        alpha,
-                                        (red as f32 * color.x) as u8,
-                                        (green as f32 * color.y) as u8,
-                                        (blue as f32 * color.z) as u8,
-                                    );
-                                }
-                            }
-                            None => {
-                                buffer[pixel_id] = from_u8_rgb(
-                                    (color.x * 255.0) as u8,
-                                    (color.y * 255.0) as u8,
-                                    (color.z * 255.0) as u8,
-                                );
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
-pub fn raster_mesh_2d(
-    mesh: &Mesh,
-    texture: Option<&Texture>,
-    buffer: &mut Vec<u32>,
-    z_buffer: &mut Vec<f32>,
-    viewport_size: Vec2,
-) {
-    for triangle_indices in &mesh.triangle_indices {
-        let vertices = mesh.get_vertices_from_triangle_indices(*triangle_indices);
-        let triangle = Triangle {
-            v0: vertices[0],
-            v1: vertices[1],
-            v2: vertices[2],
-        };
-        raster_triangle_2d(&triangle, texture, buffer, z_buffer, viewport_size);
-    }
+pub fn load_obj(path: &Path) -> Result<Mesh, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    Mesh::load_from_obj(&source)
 }
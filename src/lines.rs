@@ -0,0 +1,633 @@
+use crate::{
+    argb_to_vec4, clip_cull_triangle, clip_planes, clip_segment, cull_triangle_backface,
+    is_front_facing, outcode, vec4_to_argb, ClipResult, ClipVertex, Fragment, FragmentShader,
+    Framebuffer, FramebufferBand, Mesh, PipelineState, PolygonMode, RasterStats, RenderSettings,
+    StencilFace, StencilState, Texture, Tile, Triangle, Varying, VertexShader, Viewport,
+};
+use glam::{Vec2, Vec3, Vec3Swizzles, Vec4, Vec4Swizzles};
+
+// window position and depth of a clip space position, the same mapping the triangle setup uses
+pub fn clip_to_window(pos: Vec4, viewport: &Viewport) -> Vec3 {
+    let ndc = pos / pos.w;
+    viewport.to_window(ndc.xy()).extend(ndc.z)
+}
+
+// calls `visit(x, y, t)` for the pixels of the line from `a` to `b` inside a `width` x `height` target,
+// `t` is where the pixel lies along the line, from 0 at `a` to 1 at `b`
+// like Bresenham there is one pixel per column, or per row for steep lines, and a line
+// `line_width` wide gets that many pixels across
+pub fn line_pixels(
+    a: Vec2,
+    b: Vec2,
+    line_width: f32,
+    width: usize,
+    height: usize,
+    mut visit: impl FnMut(usize, usize, f32),
+) {
+    let d = b - a;
+    let x_major = d.x.abs() >= d.y.abs();
+    // (along, across) the line
+    let (a_major, a_minor, d_major, d_minor, major_len, minor_len) = if x_major {
+        (a.x, a.y, d.x, d.y, width, height)
+    } else {
+        (a.y, a.x, d.y, d.x, height, width)
+    };
+    let brush = line_width.round().max(1.0) as i64;
+    // only the part on screen is walked, however long the line is
+    let first = a_major.min(a_major + d_major).floor().max(0.0) as i64;
+    let last = a_major
+        .max(a_major + d_major)
+        .floor()
+        .min(major_len as f32 - 1.0) as i64;
+    for major in first..=last {
+        let t = if d_major == 0.0 {
+            0.0
+        } else {
+            ((major as f32 + 0.5 - a_major) / d_major).clamp(0.0, 1.0)
+        };
+        let center = (a_minor + d_minor * t).floor() as i64;
+        for minor in center - (brush - 1) / 2..center - (brush - 1) / 2 + brush {
+            if minor < 0 || minor >= minor_len as i64 {
+                continue;
+            }
+            let (x, y) = if x_major {
+                (major, minor)
+            } else {
+                (minor, major)
+            };
+            visit(x as usize, y as usize, t);
+        }
+    }
+}
+
+// calls `visit(x, y, uv)` for the pixels of a `size` wide square centered on `center` that are
+// inside a `width` x `height` target, `uv` goes from 0 to 1 across the square
+pub fn point_pixels(
+    center: Vec2,
+    size: f32,
+    width: usize,
+    height: usize,
+    mut visit: impl FnMut(usize, usize, Vec2),
+) {
+    let size = size.max(1.0);
+    let corner = center - size / 2.0;
+    // the pixels whose centers are in the square
+    let first = (corner - 0.5).ceil();
+    let count = size.round() as i64;
+    for y in first.y as i64..first.y as i64 + count {
+        for x in first.x as i64..first.x as i64 + count {
+            if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                continue;
+            }
+            let uv = (Vec2::new(x as f32, y as f32) + 0.5 - corner) / size;
+            visit(x as usize, y as usize, uv.clamp(Vec2::ZERO, Vec2::ONE));
+        }
+    }
+}
+
+// a line or point covers all the samples of its pixels, at the same depth
+// the viewport and scissor test, then the stencil and depth test of every sample, the stencil ops
+// of the samples that fail run right away
+// returns the index of the pixel and the samples that passed as a bitmask, None if none did
+fn test_fragment(
+    target: &mut FramebufferBand,
+    pipeline: &PipelineState,
+    stencil: Option<&(StencilState, StencilFace)>,
+    bounds: &Tile,
+    x: usize,
+    y: usize,
+    depth: f32,
+) -> Option<(usize, u32)> {
+    if !bounds.contains(x, y) {
+        return None;
+    }
+    let id = target.index(x, y);
+    let compare = target.depth_range.depth_compare(pipeline.depth.compare);
+    let mut passed = 0;
+    for s in 0..target.samples.count() {
+        let mut sample_passed = compare.test(depth, target.depth[id + s]);
+        if let (Some((state, face)), Some(stencil)) = (stencil, target.stencil.as_deref_mut()) {
+            sample_passed = state.test_and_update(face, sample_passed, &mut stencil[id + s]);
+        }
+        passed |= (sample_passed as u32) << s;
+    }
+    (passed != 0).then_some((id, passed))
+}
+
+// the samples of a fragment that passed `test_fragment` go into the fragment lists when the draw
+// collects them, otherwise they get the depth write and blending, then the stencil pass op
+fn write_fragment(
+    target: &mut FramebufferBand,
+    pipeline: &PipelineState,
+    stencil: Option<&(StencilState, StencilFace)>,
+    id: usize,
+    passed: u32,
+    depth: f32,
+    color: u32,
+) {
+    let collect = target.collects_fragments(pipeline);
+    for s in (0..target.samples.count()).filter(|s| passed & (1 << s) != 0) {
+        if let (true, Some(fragments)) = (collect, target.fragments.as_deref_mut()) {
+            fragments[id + s].push(Fragment { depth, color });
+        } else {
+            if pipeline.depth.write {
+                target.depth[id + s] = depth;
+            }
+            target.color[id + s] = pipeline.blend_color(color, target.color[id + s]);
+        }
+        if let (Some((state, face)), Some(stencil)) = (stencil, target.stencil.as_deref_mut()) {
+            state.apply(face.pass_op, &mut stencil[id + s]);
+        }
+    }
+}
+
+// a line between two points in window coordinates, z is the depth
+// uses the line width, stencil, depth test and blending of `pipeline`, of the depth bias only the constant
+// lines count as front facing for the stencil, and go into the fragment lists like triangles
+// the viewport only limits where it is drawn, the points are not mapped through it
+pub fn raster_line(
+    a: Vec3,
+    b: Vec3,
+    color: u32,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) {
+    let Some(bounds) = pipeline.pixel_bounds(framebuffer.size()) else {
+        return;
+    };
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let bias = pipeline.depth.bias.constant;
+    let mut target = framebuffer.as_band_mut();
+    let stencil = target.stencil_state(pipeline, true);
+    line_pixels(
+        a.xy(),
+        b.xy(),
+        pipeline.line_width,
+        width,
+        height,
+        |x, y, t| {
+            let depth = a.z + (b.z - a.z) * t + bias;
+            let tested = test_fragment(
+                &mut target,
+                pipeline,
+                stencil.as_ref(),
+                &bounds,
+                x,
+                y,
+                depth,
+            );
+            if let Some((id, passed)) = tested {
+                write_fragment(
+                    &mut target,
+                    pipeline,
+                    stencil.as_ref(),
+                    id,
+                    passed,
+                    depth,
+                    color,
+                );
+            }
+        },
+    );
+}
+
+// a line between two clip space positions, cut to the view frustum first
+pub fn raster_clip_line(
+    a: Vec4,
+    b: Vec4,
+    color: u32,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) {
+    let Some((t0, t1)) = clip_segment(a, b, &clip_planes(1.0)) else {
+        return;
+    };
+    let viewport = pipeline.viewport_on(framebuffer.size());
+    let (a, b) = (a.lerp(b, t0), a.lerp(b, t1));
+    raster_line(
+        clip_to_window(a, &viewport),
+        clip_to_window(b, &viewport),
+        color,
+        pipeline,
+        framebuffer,
+    );
+}
+
+// a `point_size` wide square in window coordinates, the texture is stretched over it and
+// multiplied with `color`, so with a texture with alpha and blending points can have any shape
+// like lines, points count as front facing for the stencil
+pub fn raster_point_sprite(
+    center: Vec3,
+    color: u32,
+    texture: Option<&Texture>,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) {
+    point_sprite(center, color, texture, true, pipeline, framebuffer);
+}
+
+fn point_sprite(
+    center: Vec3,
+    color: u32,
+    texture: Option<&Texture>,
+    front_facing: bool,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) {
+    let Some(bounds) = pipeline.pixel_bounds(framebuffer.size()) else {
+        return;
+    };
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let depth = center.z + pipeline.depth.bias.constant;
+    let mut target = framebuffer.as_band_mut();
+    let stencil = target.stencil_state(pipeline, front_facing);
+    point_pixels(
+        center.xy(),
+        pipeline.point_size,
+        width,
+        height,
+        |x, y, uv| {
+            let tested = test_fragment(
+                &mut target,
+                pipeline,
+                stencil.as_ref(),
+                &bounds,
+                x,
+                y,
+                depth,
+            );
+            let Some((id, passed)) = tested else {
+                return;
+            };
+            let color = match texture {
+                Some(texture) => {
+                    vec4_to_argb(argb_to_vec4(color) * argb_to_vec4(texture.rgb_at_uv(uv.x, uv.y)))
+                }
+                None => color,
+            };
+            write_fragment(
+                &mut target,
+                pipeline,
+                stencil.as_ref(),
+                id,
+                passed,
+                depth,
+                color,
+            );
+        },
+    );
+}
+
+// one edge of a clipped triangle, shaded at every pixel with perspective correct varyings
+// the stencil face is the one of the triangle
+fn shade_line<U, V: Varying, FS: FragmentShader<U, V>>(
+    (a, b): (&ClipVertex<V>, &ClipVertex<V>),
+    front_facing: bool,
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) {
+    let Some(bounds) = pipeline.pixel_bounds(framebuffer.size()) else {
+        return;
+    };
+    let viewport = pipeline.viewport_on(framebuffer.size());
+    let (wa, wb) = (
+        clip_to_window(a.pos, &viewport),
+        clip_to_window(b.pos, &viewport),
+    );
+    let (rec_a, rec_b) = (1.0 / a.pos.w, 1.0 / b.pos.w);
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let bias = pipeline.depth.bias.constant;
+    let mut target = framebuffer.as_band_mut();
+    let stencil = target.stencil_state(pipeline, front_facing);
+    line_pixels(
+        wa.xy(),
+        wb.xy(),
+        pipeline.line_width,
+        width,
+        height,
+        |x, y, t| {
+            let depth = wa.z + (wb.z - wa.z) * t + bias;
+            let tested = test_fragment(
+                &mut target,
+                pipeline,
+                stencil.as_ref(),
+                &bounds,
+                x,
+                y,
+                depth,
+            );
+            let Some((id, passed)) = tested else {
+                return;
+            };
+            // `t` is linear in screen space, the varyings are linear in 1/w
+            let rec = rec_a + (rec_b - rec_a) * t;
+            let varyings = a.varyings.lerp(b.varyings, t * rec_b / rec);
+            let frag_coord = Vec4::new(x as f32 + 0.5, y as f32 + 0.5, depth, rec);
+            if let Some(color) = fragment_shader.fragment(frag_coord, &varyings, uniforms) {
+                write_fragment(
+                    &mut target,
+                    pipeline,
+                    stencil.as_ref(),
+                    id,
+                    passed,
+                    depth,
+                    color,
+                );
+            }
+        },
+    );
+}
+
+// one corner of a triangle, shaded once for the whole point
+fn shade_point<U, V, FS: FragmentShader<U, V>>(
+    vertex: &ClipVertex<V>,
+    front_facing: bool,
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) {
+    let center = clip_to_window(vertex.pos, &pipeline.viewport_on(framebuffer.size()));
+    let frag_coord = center.extend(1.0 / vertex.pos.w);
+    if let Some(color) = fragment_shader.fragment(frag_coord, &vertex.varyings, uniforms) {
+        point_sprite(center, color, None, front_facing, pipeline, framebuffer);
+    }
+}
+
+// `PolygonMode::Line` and `PolygonMode::Point` for a whole mesh, single threaded
+// triangles are clipped and culled like filled ones, then lines are drawn along the outline of
+// what is left of them, edges the clipper added included, and points at the corners inside the view
+// the stencil face of the lines and points is the one of their triangle
+// returns how many triangles were drawn
+pub fn raster_mesh_outline<U, VS, FS>(
+    mesh: &Mesh,
+    vertex_shader: &VS,
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    settings: &RenderSettings,
+    framebuffer: &mut Framebuffer,
+) -> RasterStats
+where
+    VS: VertexShader<U>,
+    FS: FragmentShader<U, VS::Varyings>,
+{
+    let pipeline = &pipeline.for_material(&mesh.material);
+    let viewport = pipeline.viewport_on(framebuffer.size());
+    let planes = clip_planes(1.0);
+    let guard_band = settings.guard_band_for(&viewport);
+    let clip_vertices: Vec<ClipVertex<VS::Varyings>> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| vertex_shader.vertex(vertex, uniforms))
+        .collect();
+
+    let mut stats = RasterStats::default();
+    for indices in &mesh.triangle_indices {
+        let triangle = Triangle::new(
+            clip_vertices[indices.x as usize],
+            clip_vertices[indices.y as usize],
+            clip_vertices[indices.z as usize],
+        );
+        let polygon = match clip_cull_triangle(&triangle, guard_band) {
+            ClipResult::None => continue,
+            ClipResult::One(triangle) => vec![triangle.v0, triangle.v1, triangle.v2],
+            ClipResult::Fan(polygon) => polygon,
+        };
+        // the clipped polygon is convex and keeps the winding, so its area decides the facing
+        let window: Vec<Vec2> = polygon
+            .iter()
+            .map(|v| clip_to_window(v.pos, &viewport).xy())
+            .collect();
+        let area: f32 = (0..window.len())
+            .map(|i| window[i].perp_dot(window[(i + 1) % window.len()]))
+            .sum();
+        if cull_triangle_backface(area, pipeline.cull_mode, pipeline.front_face) {
+            continue;
+        }
+        stats.triangles += 1;
+        let front_facing = is_front_facing(area, pipeline.front_face);
+
+        if pipeline.polygon_mode == PolygonMode::Point {
+            for vertex in [triangle.v0, triangle.v1, triangle.v2] {
+                if outcode(vertex.pos, &planes) == 0 {
+                    shade_point(
+                        &vertex,
+                        front_facing,
+                        fragment_shader,
+                        uniforms,
+                        pipeline,
+                        framebuffer,
+                    );
+                }
+            }
+        } else {
+            for (i, a) in polygon.iter().enumerate() {
+                let b = &polygon[(i + 1) % polygon.len()];
+                shade_line(
+                    (a, b),
+                    front_facing,
+                    fragment_shader,
+                    uniforms,
+                    pipeline,
+                    framebuffer,
+                );
+            }
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{UVec3, Vec2, Vec3, Vec4};
+
+    fn pixels(a: Vec2, b: Vec2, width: f32) -> Vec<(usize, usize)> {
+        let mut pixels = Vec::new();
+        line_pixels(a, b, width, 16, 16, |x, y, _| pixels.push((x, y)));
+        pixels
+    }
+
+    #[test]
+    fn one_pixel_per_step() {
+        let shallow = pixels(Vec2::new(0.5, 0.5), Vec2::new(10.5, 3.5), 1.0);
+        assert_eq!(shallow.len(), 11);
+        assert!(shallow.iter().enumerate().all(|(i, &(x, _))| x == i));
+        assert_eq!((shallow[0], shallow[10]), ((0, 0), (10, 3)));
+
+        let steep = pixels(Vec2::new(3.5, 0.5), Vec2::new(1.5, 8.5), 1.0);
+        assert_eq!(steep.len(), 9);
+        assert!(steep.iter().enumerate().all(|(i, &(_, y))| y == i));
+
+        // wider lines get more pixels across, and only what is on screen is visited
+        let wide = pixels(Vec2::new(-100.0, 5.5), Vec2::new(200.0, 5.5), 3.0);
+        assert_eq!(wide.len(), 16 * 3);
+        assert!(wide.iter().all(|&(_, y)| (4..=6).contains(&y)));
+        assert!(pixels(Vec2::new(-10.0, 5.0), Vec2::new(-1.0, 9.0), 1.0).is_empty());
+    }
+
+    #[test]
+    fn lines_are_depth_tested() {
+        let mut framebuffer = Framebuffer::new(16, 16);
+        framebuffer.clear(0);
+        framebuffer.clear_depth(0.5);
+        let pipeline = PipelineState {
+            line_width: 2.0,
+            ..Default::default()
+        };
+        let (a, b) = (Vec2::new(0.0, 8.0), Vec2::new(16.0, 8.0));
+        raster_line(
+            a.extend(0.7),
+            b.extend(0.7),
+            0xff,
+            &pipeline,
+            &mut framebuffer,
+        );
+        assert!(framebuffer.color().iter().all(|&c| c == 0));
+        raster_line(
+            a.extend(0.3),
+            b.extend(0.3),
+            0xff,
+            &pipeline,
+            &mut framebuffer,
+        );
+        assert_eq!(
+            framebuffer.color().iter().filter(|&&c| c == 0xff).count(),
+            32
+        );
+        assert_eq!(framebuffer.depth_at(5, 8), Some(0.3));
+
+        // points too
+        let pipeline = PipelineState {
+            point_size: 4.0,
+            ..Default::default()
+        };
+        framebuffer.clear(0);
+        framebuffer.clear_depth(0.5);
+        let center = Vec3::new(8.0, 8.0, 0.3);
+        raster_point_sprite(center, 0xff, None, &pipeline, &mut framebuffer);
+        raster_point_sprite(
+            Vec3::new(2.0, 2.0, 0.7),
+            0xff,
+            None,
+            &pipeline,
+            &mut framebuffer,
+        );
+        assert_eq!(
+            framebuffer.color().iter().filter(|&&c| c == 0xff).count(),
+            16
+        );
+        assert_eq!(framebuffer.color_at(6, 6), Some(0xff));
+        assert_eq!(framebuffer.color_at(10, 10), Some(0));
+    }
+
+    #[test]
+    fn lines_use_the_stencil_and_fragment_lists() {
+        let mut framebuffer = Framebuffer::new(16, 16)
+            .with_stencil()
+            .with_fragment_lists();
+        framebuffer.clear(0);
+        // the left half is marked with 1
+        for x in 0..8 {
+            framebuffer.stencil_mut().unwrap()[coords_to_index(x, 8, 16)] = 1;
+        }
+        let (a, b) = (Vec3::new(0.0, 8.5, 0.5), Vec3::new(16.0, 8.5, 0.5));
+        let masked = PipelineState {
+            stencil: Some(StencilState {
+                reference: 1,
+                ..StencilState::new(StencilFace::new(
+                    CompareFunction::Equal,
+                    StencilOp::Increment,
+                ))
+            }),
+            ..Default::default()
+        };
+        raster_line(a, b, 0xff, &masked, &mut framebuffer);
+        let lit: Vec<usize> = (0..16)
+            .filter(|&x| framebuffer.color_at(x, 8) == Some(0xff))
+            .collect();
+        assert_eq!(lit, (0..8).collect::<Vec<_>>());
+        assert!((0..8).all(|x| framebuffer.stencil().unwrap()[coords_to_index(x, 8, 16)] == 2));
+
+        // transparent lines are collected and only show up once the lists are resolved
+        let glass = PipelineState {
+            blend: Some(BlendState::ALPHA_BLENDING),
+            order_independent: true,
+            ..Default::default()
+        };
+        let half_white = from_u8_argb(128, 255, 255, 255);
+        raster_line(a, b, half_white, &glass, &mut framebuffer);
+        assert_eq!(framebuffer.color_at(12, 8), Some(0));
+        let id = framebuffer.index(12, 8).unwrap();
+        assert_eq!(framebuffer.fragments().unwrap()[id].len(), 1);
+        framebuffer.resolve_fragments();
+        assert_ne!(framebuffer.color_at(12, 8), Some(0));
+    }
+
+    fn draw(mesh: &Mesh, polygon_mode: PolygonMode) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(32, 32);
+        framebuffer.clear(0);
+        let uniforms = DefaultUniforms::new(glam::Mat4::IDENTITY, glam::Mat4::IDENTITY, None);
+        raster_mesh(
+            mesh,
+            &DefaultShader,
+            &DefaultShader,
+            &uniforms,
+            &PipelineState {
+                polygon_mode,
+                point_size: 3.0,
+                ..Default::default()
+            },
+            &RenderSettings::default(),
+            &mut framebuffer,
+        );
+        framebuffer
+    }
+
+    #[test]
+    fn polygon_modes() {
+        let vertex =
+            |x: f32, y: f32| Vertex::new(Vec4::new(x, y, 0.5, 1.0), Vec3::Z, Vec3::ONE, Vec2::ZERO);
+        // a counter clockwise quad and a clockwise triangle that gets culled
+        let mesh = Mesh {
+            vertices: vec![
+                vertex(-0.5, -0.5),
+                vertex(0.5, -0.5),
+                vertex(-0.5, 0.5),
+                vertex(0.5, 0.5),
+                vertex(0.8, 0.8),
+                vertex(0.9, 0.8),
+            ],
+            triangle_indices: vec![
+                UVec3::new(0, 1, 2),
+                UVec3::new(1, 3, 2),
+                UVec3::new(3, 4, 5),
+            ],
+            ..Default::default()
+        };
+        let lit = |framebuffer: &Framebuffer, x, y| framebuffer.color_at(x, y) != Some(0);
+
+        let fill = draw(&mesh, PolygonMode::Fill);
+        let line = draw(&mesh, PolygonMode::Line);
+        // the outline and the diagonal, but not the inside
+        assert!(lit(&line, 8, 16) && lit(&line, 16, 8) && lit(&line, 24, 16));
+        assert_eq!((0..32).filter(|&x| lit(&line, x, 16)).count(), 3);
+        assert!(!lit(&line, 12, 20));
+        assert!(lit(&fill, 12, 20));
+        // nothing outside of what fill draws
+        for y in 0..32 {
+            for x in 0..32 {
+                assert!(!lit(&line, x, y) || lit(&fill, x, y) || x == 24 || y == 24);
+            }
+        }
+        assert!(!lit(&line, 28, 26));
+
+        let point = draw(&mesh, PolygonMode::Point);
+        let lit_pixels = point.color().iter().filter(|&&c| c != 0).count();
+        assert_eq!(lit_pixels, 4 * 9);
+        assert!(lit(&point, 8, 8) && lit(&point, 24, 24) && !lit(&point, 16, 16));
+    }
+}
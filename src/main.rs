@@ -1,13 +1,11 @@
 use glam::{Vec2, Vec3};
-use minifb::{Key, Window, WindowOptions};
-use std::path::Path;
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
 
 use rusterizer::*;
 pub mod font;
+pub mod options;
 pub use font::Font;
-
-const WIDTH: usize = 640;
-const HEIGHT: usize = 360;
+pub use options::{Options, USAGE};
 
 fn input_handling(dt: f32, window: &Window, camera: &mut Camera) {
     let mut axis = Vec3::new(0.0, 0.0, 0.0);
@@ -35,15 +33,137 @@ fn input_handling(dt: f32, window: &Window, camera: &mut Camera) {
         + camera.transform.up() * camera.speed * axis.z;
 }
 
+// the model and the texture it is drawn with, `--texture` replaces the one of a gltf
+fn load_model(options: &Options) -> Result<(Mesh, Option<Texture>), String> {
+    let extension = options
+        .model
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let (mesh, texture) = match extension.as_deref() {
+        Some("gltf") | Some("glb") => {
+            load_gltf_textured(&options.model).map_err(|e| e.to_string())?
+        }
+        Some("obj") => (load_obj(&options.model)?, None),
+        _ => return Err("models have to be .gltf, .glb or .obj files".to_string()),
+    };
+    match &options.texture {
+        Some(path) if !path.exists() => Err(format!("{} does not exist", path.display())),
+        Some(path) => {
+            let texture =
+                Texture::try_load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok((mesh, Some(texture)))
+        }
+        None => Ok((mesh, texture)),
+    }
+}
+
+// the 3D part of a frame is drawn into `msaa_framebuffer` when there is one, it only exists while msaa is on
+fn scene_target<'a>(
+    framebuffer: &'a mut Framebuffer,
+    msaa_framebuffer: Option<&'a mut Framebuffer>,
+) -> &'a mut Framebuffer {
+    msaa_framebuffer.unwrap_or(framebuffer)
+}
+
+fn draw_model(
+    model: &Mesh,
+    uniforms: &DefaultUniforms,
+    pipeline: &PipelineState,
+    settings: &RenderSettings,
+    background: u32,
+    target: &mut Framebuffer,
+) -> RasterStats {
+    target.clear(background);
+    raster_mesh(
+        model,
+        &DefaultShader,
+        &DefaultShader,
+        uniforms,
+        pipeline,
+        settings,
+        target,
+    )
+}
+
+// the scene drawn so far into the single sampled framebuffer, anti-aliased
+fn finish_scene(
+    settings: &RenderSettings,
+    framebuffer: &mut Framebuffer,
+    msaa_framebuffer: Option<&Framebuffer>,
+) {
+    if let Some(msaa_framebuffer) = msaa_framebuffer {
+        msaa_framebuffer.resolve(framebuffer);
+    }
+    if settings.fxaa {
+        fxaa(framebuffer);
+    }
+}
+
+fn model_transform(rot: f32) -> glam::Mat4 {
+    Transform::from_rotation(glam::Quat::from_euler(glam::EulerRot::XYZ, rot, 0.0, 0.0)).local()
+}
+
 fn main() {
-    let grey = from_u8_rgb(100, 100, 200);
-    let mut buffer: Vec<u32> = vec![grey; WIDTH * HEIGHT];
-    let mut z_buffer = vec![f32::INFINITY; WIDTH * HEIGHT];
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let options = Options::parse(args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(2);
+    });
+    let (model, texture) = load_model(&options).unwrap_or_else(|e| {
+        eprintln!("can't load {}: {}", options.model.display(), e);
+        std::process::exit(1);
+    });
+    let (width, height) = (options.width, options.height);
+    let background = options.background;
+
+    let mut camera = Camera {
+        aspect_ratio: width as f32 / height as f32,
+        transform: Transform::from_translation(options.camera),
+        frustum_near: 0.5,
+        frustum_far: 100.0,
+        ..Default::default()
+    };
+
+    let mut pipeline = PipelineState::default();
+    let mut settings = RenderSettings::default();
+    let mut framebuffer = Framebuffer::new(width, height).with_depth_range(camera.depth_range);
+    // msaa starts off, the multisampled framebuffer is only made when it is turned on
+    let mut msaa_framebuffer: Option<Framebuffer> = None;
+
+    // headless, a single frame straight to disk
+    if let Some(output) = &options.output {
+        let model_local = model_transform(0.0);
+        let mvp = camera.projection() * camera.view() * model_local;
+        let uniforms = DefaultUniforms::new(mvp, model_local, texture.as_ref());
+        draw_model(
+            &model,
+            &uniforms,
+            &pipeline,
+            &settings,
+            background,
+            scene_target(&mut framebuffer, msaa_framebuffer.as_mut()),
+        );
+        finish_scene(&settings, &mut framebuffer, msaa_framebuffer.as_ref());
+        let saved = save_color(&framebuffer, output).and_then(|_| match &options.depth_output {
+            Some(depth_output) => save_depth(&framebuffer, depth_output),
+            None => Ok(()),
+        });
+        if let Err(e) = saved {
+            eprintln!("can't save the frame: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     let mut window = Window::new(
         "Rusterizer - ESC to exit",
-        WIDTH,
-        HEIGHT,
+        width,
+        height,
         WindowOptions::default(),
     )
     .unwrap_or_else(|e| {
@@ -53,64 +173,87 @@ fn main() {
     // Limit to max ~60 fps update rate
     // window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
 
-    let texture = Texture::load(Path::new("assets/textures/bee_icon_256.png"));
-    let model = load_gltf(Path::new(
-        "assets/gltf_models/damaged_helmet/DamagedHelmet.gltf",
-    ));
-    let window_size = glam::vec2(WIDTH as f32, HEIGHT as f32);
-
-    let aspect_ratio = WIDTH as f32 / HEIGHT as f32;
-    let mut camera = Camera {
-        aspect_ratio,
-        transform: Transform::from_translation(glam::vec3(0.0, 0.0, 5.0)),
-        frustum_near: 0.5,
-        frustum_far: 100.0,
-        ..Default::default()
-    };
-
     // has to be mutable because of how it's implemented
     let mut font = Font::default();
+    let mut debug = DebugDraw::default();
+    let mut show_debug = false;
 
     let mut rot = 0.0;
 
     let now = std::time::Instant::now();
     let mut start_time = now.elapsed().as_secs_f32();
     let mut fps_timer = 0.0;
+    let mut stats = RasterStats::default();
 
     while window.is_open() && !window.is_key_down(Key::Escape) {
         let end_time = now.elapsed().as_secs_f32();
         let dt = end_time - start_time;
         fps_timer += dt;
         if fps_timer > 1.0 {
-            println!("{}", (fps_timer / dt) as u32);
+            println!(
+                "{} fps, hi-z culled {}/{} triangles",
+                (fps_timer / dt) as u32,
+                stats.culled(),
+                stats.triangles
+            );
             fps_timer = 0.0;
         }
 
         input_handling(dt, &window, &mut camera);
-        buffer.fill(grey);
-        z_buffer.fill(f32::INFINITY);
-        let parent_local =
-            Transform::from_rotation(glam::Quat::from_euler(glam::EulerRot::XYZ, rot, 0.0, 0.0))
-                .local();
+        if window.is_key_pressed(Key::C, KeyRepeat::No) {
+            settings.clip_visualization = !settings.clip_visualization;
+        }
+        if window.is_key_pressed(Key::F, KeyRepeat::No) {
+            settings.fxaa = !settings.fxaa;
+        }
+        // cycles through filled, wireframe and points
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            pipeline.polygon_mode = match pipeline.polygon_mode {
+                PolygonMode::Fill => PolygonMode::Line,
+                PolygonMode::Line => PolygonMode::Point,
+                PolygonMode::Point => PolygonMode::Fill,
+            };
+        }
+        if window.is_key_pressed(Key::G, KeyRepeat::No) {
+            show_debug = !show_debug;
+        }
+        // cycles through no msaa, 2x, 4x and 8x
+        if window.is_key_pressed(Key::M, KeyRepeat::No) {
+            settings.msaa = match settings.msaa.map(|pattern| pattern.count()) {
+                None => Some(SamplePattern::standard(2)),
+                Some(8) => None,
+                Some(count) => Some(SamplePattern::standard(count * 2)),
+            };
+            msaa_framebuffer = settings.msaa.map(|pattern| {
+                Framebuffer::with_samples(width, height, pattern)
+                    .with_depth_range(camera.depth_range)
+            });
+        }
+        let parent_local = model_transform(rot);
         let mvp = camera.projection() * camera.view() * parent_local;
 
-        raster_mesh(
-            &model,
-            &(mvp),
-            &parent_local,
-            Some(&texture),
-            &mut buffer,
-            &mut z_buffer,
-            window_size,
-        );
+        let uniforms = DefaultUniforms::new(mvp, parent_local, texture.as_ref());
+        let target = scene_target(&mut framebuffer, msaa_framebuffer.as_mut());
+        stats = draw_model(&model, &uniforms, &pipeline, &settings, background, target);
+        // the debug lines are depth tested against the scene, so they go into the same framebuffer
+        // before it is resolved
+        if show_debug {
+            debug.grid(20, 1.0, from_u8_rgb(96, 96, 96));
+            debug.axes(&Transform::IDENTITY, 2.0);
+            debug.render(camera.projection() * camera.view(), target);
+        }
+        // fxaa runs in there, before the text, so the overlay stays sharp
+        finish_scene(&settings, &mut framebuffer, msaa_framebuffer.as_ref());
 
         let _text_mvp = camera.projection() * camera.view() * glam::Mat4::IDENTITY;
-        let text_pos = Vec2::new(50.0, HEIGHT as f32 / 2.0);
+        let text_pos = Vec2::new(50.0, height as f32 / 2.0);
         font.text("The coolest rasterizer ever!".to_string(), text_pos);
-        font.render(&mut buffer, &mut z_buffer, window_size);
+        font.render(&mut framebuffer);
 
-        rot += 0.5 * dt as f32;
+        rot += 0.5 * dt;
         start_time = end_time;
-        window.update_with_buffer(&buffer, WIDTH, HEIGHT).unwrap();
+        window
+            .update_with_buffer(framebuffer.color(), width, height)
+            .unwrap();
     }
 }
@@ -0,0 +1,58 @@
+// surface properties of a mesh that change how it is rasterized, rather than how it is shaded
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Material {
+    // both sides are visible, backface culling is skipped
+    pub double_sided: bool,
+    // glTF `BLEND` alpha mode, drawn with alpha blending instead of overwriting
+    pub blend: bool,
+    // with `blend`, resolved per pixel from the framebuffer fragment lists instead of in draw order,
+    // for transparent meshes that intersect or can't be sorted
+    pub order_independent: bool,
+}
+
+impl Material {
+    pub fn from_gltf(material: &gltf::Material) -> Self {
+        Self {
+            double_sided: material.double_sided(),
+            blend: material.alpha_mode() == gltf::material::AlphaMode::Blend,
+            // glTF doesn't say anything about draw order, so blended materials are sorted per pixel
+            // whenever the framebuffer has fragment lists
+            order_independent: material.alpha_mode() == gltf::material::AlphaMode::Blend,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn gltf_alpha_modes() {
+        let gltf = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "materials": [
+                    { "alphaMode": "BLEND" },
+                    { "alphaMode": "MASK", "doubleSided": true },
+                    {}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let states: Vec<PipelineState> = gltf
+            .materials()
+            .map(|material| PipelineState::default().for_material(&Material::from_gltf(&material)))
+            .collect();
+
+        assert_eq!(states[0].blend, Some(BlendState::ALPHA_BLENDING));
+        assert!(states[0].order_independent);
+        assert!(!states[0].depth.write);
+        for state in &states[1..] {
+            assert_eq!(state.blend, None);
+            assert!(!state.order_independent);
+            assert!(state.depth.write);
+        }
+        assert_eq!(states[1].cull_mode, CullMode::None);
+        assert_eq!(states[2].cull_mode, PipelineState::default().cull_mode);
+    }
+}
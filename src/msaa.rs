@@ -0,0 +1,349 @@
+use crate::{
+    raster, Fragment, FragmentShader, FramebufferBand, PipelineState, ScreenTriangle, Tile, Varying,
+};
+use glam::{Vec2, Vec4};
+
+pub const MAX_SAMPLES: usize = 8;
+
+// sample positions inside a pixel, as offsets from the pixel center in pixels
+// every offset must be a multiple of 1/256 so it lands exactly on the subpixel grid
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SamplePattern {
+    count: usize,
+    offsets: [Vec2; MAX_SAMPLES],
+}
+
+impl SamplePattern {
+    pub fn new(offsets: &[Vec2]) -> Self {
+        assert!(
+            (1..=MAX_SAMPLES).contains(&offsets.len()),
+            "a sample pattern needs between 1 and {} samples",
+            MAX_SAMPLES
+        );
+        let mut pattern = Self {
+            count: offsets.len(),
+            offsets: [Vec2::ZERO; MAX_SAMPLES],
+        };
+        pattern.offsets[..offsets.len()].copy_from_slice(offsets);
+        pattern
+    }
+
+    // one sample in the pixel center, no anti-aliasing
+    pub fn single() -> Self {
+        Self::new(&[Vec2::ZERO])
+    }
+
+    // the standard Direct3D / Vulkan patterns for 2, 4 and 8 samples, given in 1/16 of a pixel
+    pub fn standard(count: usize) -> Self {
+        let sixteenths: &[(f32, f32)] = match count {
+            1 => &[(0.0, 0.0)],
+            2 => &[(4.0, 4.0), (-4.0, -4.0)],
+            4 => &[(-2.0, -6.0), (6.0, -2.0), (-6.0, 2.0), (2.0, 6.0)],
+            8 => &[
+                (1.0, -3.0),
+                (-1.0, 3.0),
+                (5.0, 1.0),
+                (-3.0, -5.0),
+                (-5.0, 5.0),
+                (-7.0, -1.0),
+                (3.0, 7.0),
+                (7.0, -7.0),
+            ],
+            _ => panic!("no standard pattern with {} samples", count),
+        };
+        let offsets: Vec<Vec2> = sixteenths
+            .iter()
+            .map(|&(x, y)| Vec2::new(x, y) / 16.0)
+            .collect();
+        Self::new(&offsets)
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn offsets(&self) -> &[Vec2] {
+        &self.offsets[..self.count]
+    }
+
+    // the offsets on the subpixel grid
+    pub fn fixed_offsets(&self) -> [(i64, i64); MAX_SAMPLES] {
+        self.offsets.map(raster::to_fixed_vec)
+    }
+}
+
+// multisampled version of `raster_screen_triangle`
+// coverage and the depth test are done for every sample, the fragment shader runs once per pixel
+// at the pixel center and its color is stored in all the samples that passed
+pub fn raster_screen_triangle_msaa<U, V: Varying, FS: FragmentShader<U, V>>(
+    triangle: &ScreenTriangle<V>,
+    tile: &Tile,
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    target: &mut FramebufferBand,
+) -> bool {
+    let Some(rect) = triangle.tile_rect(tile) else {
+        return true;
+    };
+    let edges = &triangle.edges;
+    let [z0, z1, z2] = triangle.ndc.map(|ndc| ndc.z);
+    let [rec0, rec1, rec2] = triangle.rec;
+    let pv = &triangle.pv;
+    let samples = target.samples.count();
+    let offsets = target.samples.fixed_offsets();
+    let step_x = edges.step_x();
+    let step_y = edges.step_y();
+    let stencil = target.stencil_state(pipeline, triangle.front_facing);
+    let collect = target.collects_fragments(pipeline);
+    let depth_compare = target.depth_range.depth_compare(pipeline.depth.compare);
+    let mut discarded = false;
+
+    // edge values of every sample, stepped together with the pixel centers
+    let center = edges.eval(raster::pixel_center_fixed(rect.left, rect.top));
+    let mut e_row: [[i64; 3]; MAX_SAMPLES] = offsets.map(|(ox, oy)| {
+        [0, 1, 2].map(|i| center[i] + edges.edges[i].a * ox + edges.edges[i].b * oy)
+    });
+    let mut center_row = center;
+
+    for y in rect.top..=rect.bottom {
+        // a pixel is touched if any of its samples is, so the span is the union of the sample spans
+        let mut span: Option<(usize, usize)> = None;
+        for e in &e_row[..samples] {
+            if let Some((first, last)) = edges.row_span(*e, rect.right - rect.left + 1) {
+                span = Some(match span {
+                    Some((f, l)) => (f.min(first), l.max(last)),
+                    None => (first, last),
+                });
+            }
+        }
+        let row = e_row;
+        let center = center_row;
+        for e in e_row.iter_mut() {
+            *e = [0, 1, 2].map(|i| e[i] + step_y[i]);
+        }
+        center_row = [0, 1, 2].map(|i| center_row[i] + step_y[i]);
+        let Some((first, last)) = span else {
+            continue;
+        };
+
+        for dx in first..=last {
+            let x = rect.left + dx;
+            let pixel_id = target.index(x, y);
+            let mut mask = 0u32;
+            let mut sample_depth = [0.0; MAX_SAMPLES];
+            for s in 0..samples {
+                let e = [0, 1, 2].map(|i| row[s][i] + step_x[i] * dx as i64);
+                if !edges.covers(e) {
+                    continue;
+                }
+                let bary = edges.barycentric_from_edges(e);
+                let z = bary.x * z0 + bary.y * z1 + bary.z * z2;
+                let mut passed = depth_compare.test(z, target.depth[pixel_id + s]);
+                if let (Some((state, face)), Some(stencil)) =
+                    (&stencil, target.stencil.as_deref_mut())
+                {
+                    passed = state.test_and_update(face, passed, &mut stencil[pixel_id + s]);
+                }
+                if passed {
+                    mask |= 1 << s;
+                    sample_depth[s] = z;
+                }
+            }
+            if mask == 0 {
+                continue;
+            }
+
+            // shaded once, at the pixel center even if that is just outside of the triangle
+            let e = [0, 1, 2].map(|i| center[i] + step_x[i] * dx as i64);
+            let bary = edges.barycentric_from_edges(e);
+            let correction = 1.0 / (bary.x * rec0 + bary.y * rec1 + bary.z * rec2);
+            let varyings = pv[0]
+                .scale(bary.x)
+                .add(pv[1].scale(bary.y))
+                .add(pv[2].scale(bary.z))
+                .scale(correction);
+            let frag_coord = Vec4::new(
+                x as f32 + 0.5,
+                y as f32 + 0.5,
+                bary.x * z0 + bary.y * z1 + bary.z * z2,
+                1.0 / correction,
+            );
+            match fragment_shader.fragment(frag_coord, &varyings, uniforms) {
+                Some(fragment_color) => {
+                    for s in (0..samples).filter(|s| mask & (1 << s) != 0) {
+                        if let (true, Some(fragments)) = (collect, target.fragments.as_deref_mut())
+                        {
+                            fragments[pixel_id + s].push(Fragment {
+                                depth: sample_depth[s],
+                                color: triangle.debug_color.unwrap_or(fragment_color),
+                            });
+                        } else {
+                            if pipeline.depth.write {
+                                target.depth[pixel_id + s] = sample_depth[s];
+                            }
+                            // every sample blends with its own destination
+                            let dst = target.color[pixel_id + s];
+                            target.color[pixel_id + s] = triangle
+                                .debug_color
+                                .unwrap_or_else(|| pipeline.blend_color(fragment_color, dst));
+                        }
+                        if let (Some((state, face)), Some(stencil)) =
+                            (&stencil, target.stencil.as_deref_mut())
+                        {
+                            state.apply(face.pass_op, &mut stencil[pixel_id + s]);
+                        }
+                    }
+                }
+                None => discarded = true,
+            }
+        }
+    }
+    !discarded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use glam::{DVec2, UVec3, Vec2, Vec3, Vec4};
+
+    const SIZE: usize = 32;
+
+    // pixel positions on the subpixel grid, so the reference sees exactly what gets rasterized
+    fn triangle() -> [DVec2; 3] {
+        [
+            DVec2::new(2.0 + 37.0 / 256.0, 1.0 + 201.0 / 256.0),
+            DVec2::new(29.0 + 3.0 / 256.0, 9.0 + 77.0 / 256.0),
+            DVec2::new(11.0 + 130.0 / 256.0, 30.0 + 5.0 / 256.0),
+        ]
+    }
+
+    fn render(pattern: SamplePattern) -> Framebuffer {
+        let vertices = triangle().map(|p| {
+            let ndc = p.as_vec2() / SIZE as f32 * 2.0 - 1.0;
+            Vertex::new(
+                Vec4::new(ndc.x, ndc.y, 0.5, 1.0),
+                Vec3::Z,
+                Vec3::ONE,
+                Vec2::ZERO,
+            )
+        });
+        let mesh = Mesh {
+            vertices: vertices.to_vec(),
+            triangle_indices: vec![UVec3::new(0, 1, 2)],
+            ..Default::default()
+        };
+        let mut target = Framebuffer::with_samples(SIZE, SIZE, pattern);
+        draw(
+            &mesh,
+            WHITE,
+            &PipelineState {
+                cull_mode: CullMode::None,
+                ..Default::default()
+            },
+            &mut target,
+        );
+        target
+    }
+
+    fn cross(a: DVec2, b: DVec2) -> f64 {
+        a.x * b.y - a.y * b.x
+    }
+
+    // area of the unit pixel square at (x, y) covered by the triangle, by clipping the square
+    // against the three edges
+    fn covered_area(x: usize, y: usize) -> f64 {
+        let [v0, v1, v2] = triangle();
+        let ccw = cross(v1 - v0, v2 - v0) > 0.0;
+        let mut polygon = vec![
+            DVec2::new(x as f64, y as f64),
+            DVec2::new(x as f64 + 1.0, y as f64),
+            DVec2::new(x as f64 + 1.0, y as f64 + 1.0),
+            DVec2::new(x as f64, y as f64 + 1.0),
+        ];
+        for (a, b) in [(v0, v1), (v1, v2), (v2, v0)] {
+            let side = |p: DVec2| {
+                let c = cross(b - a, p - a);
+                if ccw {
+                    c
+                } else {
+                    -c
+                }
+            };
+            let mut clipped = Vec::new();
+            for i in 0..polygon.len() {
+                let (p, q) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                let (sp, sq) = (side(p), side(q));
+                if sp >= 0.0 {
+                    clipped.push(p);
+                }
+                if (sp >= 0.0) != (sq >= 0.0) {
+                    clipped.push(p + (q - p) * (sp / (sp - sq)));
+                }
+            }
+            polygon = clipped;
+        }
+        let area: f64 = (0..polygon.len())
+            .map(|i| cross(polygon[i], polygon[(i + 1) % polygon.len()]))
+            .sum();
+        area.abs() / 2.0
+    }
+
+    #[test]
+    fn every_sample_matches_point_in_triangle() {
+        let [v0, v1, v2] = triangle();
+        for count in [2, 4, 8] {
+            let pattern = SamplePattern::standard(count);
+            let target = render(pattern);
+            for y in 0..SIZE {
+                for x in 0..SIZE {
+                    for (s, offset) in pattern.offsets().iter().enumerate() {
+                        let p = DVec2::new(x as f64 + 0.5, y as f64 + 0.5) + offset.as_dvec2();
+                        let e = [
+                            cross(v2 - v1, p - v1),
+                            cross(v0 - v2, p - v2),
+                            cross(v1 - v0, p - v0),
+                        ];
+                        assert!(e.iter().all(|e| e.abs() > 1e-9), "sample on an edge");
+                        let inside = e.iter().all(|&e| e > 0.0) || e.iter().all(|&e| e < 0.0);
+                        let sample = target.color()[(x + y * SIZE) * count + s];
+                        assert_eq!(sample == WHITE, inside, "pixel {} {} sample {}", x, y, s);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resolved_edges_approach_area_coverage() {
+        // mean error over the pixels the edges go through, and the worst pixel
+        let error = |count: usize| {
+            let target = render(SamplePattern::standard(count));
+            let mut resolved = Framebuffer::new(SIZE, SIZE);
+            target.resolve(&mut resolved);
+            let buffer = resolved.color();
+            let (mut sum, mut max, mut pixels) = (0.0, 0.0f64, 0);
+            for y in 0..SIZE {
+                for x in 0..SIZE {
+                    let area = covered_area(x, y);
+                    if area > 0.0 && area < 1.0 {
+                        let resolved = (buffer[x + y * SIZE] & 0xff) as f64 / 255.0;
+                        let error = (resolved - area).abs();
+                        sum += error;
+                        max = max.max(error);
+                        pixels += 1;
+                    }
+                }
+            }
+            (sum / pixels as f64, max)
+        };
+        let (single, _) = error(1);
+        let (x2, _) = error(2);
+        let (x4, max4) = error(4);
+        let (x8, max8) = error(8);
+        assert!(x2 < single && x4 < x2 && x8 < x4);
+        assert!(x8 < 0.07);
+        assert!(max4 < 0.3 && max8 < 0.25);
+    }
+}
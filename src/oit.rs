@@ -0,0 +1,136 @@
+use crate::{BlendState, CompareFunction, DepthRange};
+
+// a shaded transparent fragment, kept in a per sample list until the lists are resolved
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fragment {
+    pub depth: f32,
+    pub color: u32,
+}
+
+// composites the fragments of one sample over its opaque color, back to front, and empties the list
+// sorting per sample is what lets transparent meshes be drawn in any order and even intersect,
+// fragments at the same depth keep the order they were drawn in
+// fragments behind `opaque_depth` are dropped, opaque geometry drawn after them still hides them
+pub fn resolve_fragments(
+    fragments: &mut Vec<Fragment>,
+    opaque: u32,
+    opaque_depth: f32,
+    range: DepthRange,
+) -> u32 {
+    let in_front = range.depth_compare(CompareFunction::Less);
+    fragments.retain(|fragment| in_front.test(fragment.depth, opaque_depth));
+    match range {
+        DepthRange::Standard => fragments.sort_by(|a, b| b.depth.total_cmp(&a.depth)),
+        DepthRange::Reversed => fragments.sort_by(|a, b| a.depth.total_cmp(&b.depth)),
+    }
+    fragments.drain(..).fold(opaque, |dst, fragment| {
+        BlendState::ALPHA_BLENDING.blend(fragment.color, dst)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    // full screen quad going from `left` depth on the left edge to `right` on the right one
+    fn slope(left: f32, right: f32) -> Mesh {
+        let mut mesh = quad(-1.0, 1.0, left);
+        for vertex in &mut mesh.vertices {
+            if vertex.pos.x > 0.0 {
+                vertex.pos.z = right;
+            }
+        }
+        mesh
+    }
+
+    const BACKGROUND: u32 = 0xff00_0000;
+    const RED: u32 = 0x80ff_0000;
+    const BLUE: u32 = 0x8000_00ff;
+
+    // two transparent quads crossing in the middle of the screen, drawn in the given order
+    fn intersecting(order_independent: bool, red_first: bool, samples: usize) -> Framebuffer {
+        let mut framebuffer = Framebuffer::with_samples(64, 16, SamplePattern::standard(samples))
+            .with_fragment_lists();
+        framebuffer.clear(BACKGROUND);
+        let material = Material {
+            blend: true,
+            order_independent,
+            ..Default::default()
+        };
+        let mut red = slope(0.2, 0.8);
+        let mut blue = slope(0.8, 0.2);
+        red.material = material;
+        blue.material = material;
+        let pipeline = PipelineState {
+            cull_mode: CullMode::None,
+            ..Default::default()
+        };
+        let mut layers = [(&red, RED), (&blue, BLUE)];
+        if !red_first {
+            layers.reverse();
+        }
+        for (mesh, color) in layers {
+            draw(mesh, color, &pipeline, &mut framebuffer);
+        }
+        framebuffer.resolve_fragments();
+        framebuffer
+    }
+
+    #[test]
+    fn intersecting_layers_are_sorted_per_pixel() {
+        let blend = |colors: [u32; 2]| {
+            colors.iter().fold(BACKGROUND, |dst, &src| {
+                BlendState::ALPHA_BLENDING.blend(src, dst)
+            })
+        };
+        for samples in [1, 4] {
+            let framebuffer = intersecting(true, true, samples);
+            // red is in front on the left, blue on the right
+            assert_eq!(framebuffer.color_at(4, 8), Some(blend([BLUE, RED])));
+            assert_eq!(framebuffer.color_at(60, 8), Some(blend([RED, BLUE])));
+            assert!(framebuffer.fragments().unwrap().iter().all(Vec::is_empty));
+            assert_eq!(
+                framebuffer.color(),
+                intersecting(true, false, samples).color()
+            );
+        }
+        // just blending gets one of the halves wrong whatever the order
+        assert_ne!(
+            intersecting(false, true, 1).color(),
+            intersecting(false, false, 1).color()
+        );
+    }
+
+    #[test]
+    fn opaque_geometry_hides_fragments() {
+        let mut framebuffer = Framebuffer::new(64, 16).with_fragment_lists();
+        let pipeline = PipelineState {
+            cull_mode: CullMode::None,
+            ..Default::default()
+        };
+        let mut glass = slope(0.2, 0.8);
+        glass.material.blend = true;
+        glass.material.order_independent = true;
+        // an opaque quad and a transparent one that is only in front on the left, in either order,
+        // the fragments on the right made it into the lists when the glass was drawn first
+        for opaque_first in [true, false] {
+            framebuffer.clear(BACKGROUND);
+            if opaque_first {
+                draw(&slope(0.5, 0.5), 0xff00_ff00, &pipeline, &mut framebuffer);
+                draw(&glass, RED, &pipeline, &mut framebuffer);
+            } else {
+                draw(&glass, RED, &pipeline, &mut framebuffer);
+                draw(&slope(0.5, 0.5), 0xff00_ff00, &pipeline, &mut framebuffer);
+            }
+            // nothing shows before the resolve, and the depth is the one of the opaque pass
+            assert!(framebuffer.color().iter().all(|&c| c == 0xff00_ff00));
+            assert!(framebuffer.depth().iter().all(|&z| (z - 0.5).abs() < 1e-6));
+
+            framebuffer.resolve_fragments();
+            let tinted = BlendState::ALPHA_BLENDING.blend(RED, 0xff00_ff00);
+            assert_eq!(framebuffer.color_at(4, 8), Some(tinted));
+            assert_eq!(framebuffer.color_at(60, 8), Some(0xff00_ff00));
+        }
+    }
+}
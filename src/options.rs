@@ -0,0 +1,138 @@
+use glam::Vec3;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: rusterizer [MODEL] [OPTIONS]
+
+  MODEL                    .gltf or .obj file, the DamagedHelmet by default
+
+  --texture PATH           use this texture instead of the one of the model
+  --size WIDTHxHEIGHT      window or image size, 640x360 by default
+  --background RRGGBB      clear color as hex, 6464c8 by default
+  --camera X,Y,Z           camera start position, 0,0,5 by default
+  --output PATH            render a single frame to a .png or .ppm and exit, without a window
+  --depth-output PATH      with --output, also save the depth buffer as a grayscale image
+  -h, --help               print this and exit";
+
+pub struct Options {
+    pub model: PathBuf,
+    pub texture: Option<PathBuf>,
+    pub width: usize,
+    pub height: usize,
+    pub background: u32,
+    pub camera: Vec3,
+    pub output: Option<PathBuf>,
+    pub depth_output: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            model: PathBuf::from("assets/gltf_models/damaged_helmet/DamagedHelmet.gltf"),
+            texture: None,
+            width: 640,
+            height: 360,
+            background: 0x6464c8,
+            camera: Vec3::new(0.0, 0.0, 5.0),
+            output: None,
+            depth_output: None,
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Option<(usize, usize)> {
+    let (width, height) = value.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+fn parse_color(value: &str) -> Option<u32> {
+    let hex = value.trim_start_matches('#');
+    (hex.len() == 6)
+        .then(|| u32::from_str_radix(hex, 16).ok())
+        .flatten()
+}
+
+fn parse_position(value: &str) -> Option<Vec3> {
+    let coordinates: Vec<f32> = value
+        .split(',')
+        .map(|c| c.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    (coordinates.len() == 3).then(|| Vec3::from_slice(&coordinates))
+}
+
+impl Options {
+    // the arguments without the program name, `--help` has to be handled before
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut model = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                if model.replace(PathBuf::from(&arg)).is_some() {
+                    return Err(format!("unexpected argument {}", arg));
+                }
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{} needs a value", arg))?;
+            let invalid = || format!("invalid value for {}: {}", arg, value);
+            match arg.as_str() {
+                "--texture" => options.texture = Some(PathBuf::from(&value)),
+                "--size" => {
+                    (options.width, options.height) = parse_size(&value).ok_or_else(invalid)?
+                }
+                "--background" => options.background = parse_color(&value).ok_or_else(invalid)?,
+                "--camera" => options.camera = parse_position(&value).ok_or_else(invalid)?,
+                "--output" => options.output = Some(PathBuf::from(&value)),
+                "--depth-output" => options.depth_output = Some(PathBuf::from(&value)),
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        if options.depth_output.is_some() && options.output.is_none() {
+            return Err("--depth-output only works together with --output".to_string());
+        }
+        if let Some(model) = model {
+            options.model = model;
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn all_options() {
+        let options = parse(
+            "teapot.obj --size 320x200 --texture bee.png --background #ff8000 \
+             --camera 1,-2.5,3 --output frame.png --depth-output depth.png",
+        )
+        .unwrap();
+        assert_eq!(options.model, PathBuf::from("teapot.obj"));
+        assert_eq!(options.texture, Some(PathBuf::from("bee.png")));
+        assert_eq!((options.width, options.height), (320, 200));
+        assert_eq!(options.background, 0xff8000);
+        assert_eq!(options.camera, Vec3::new(1.0, -2.5, 3.0));
+        assert_eq!(options.output, Some(PathBuf::from("frame.png")));
+        assert_eq!(options.depth_output, Some(PathBuf::from("depth.png")));
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(parse("").is_ok());
+        assert!(parse("--size 0x200").is_err());
+        assert!(parse("--size 320").is_err());
+        assert!(parse("--background fff").is_err());
+        assert!(parse("--camera 1,2").is_err());
+        assert!(parse("--output").is_err());
+        assert!(parse("--depth-output depth.png").is_err());
+        assert!(parse("--fullscreen yes").is_err());
+        assert!(parse("a.gltf b.gltf").is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use crate::{BlendState, Material, StencilState, Tile, Viewport};
+use glam::{BVec4A, Vec2, Vec4};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+}
+
+// which screen space winding counts as the front of a triangle
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrontFace {
+    CounterClockwise,
+    Clockwise,
+}
+
+// what of a triangle gets drawn, `Line` and `Point` are for looking at the topology of a mesh
+// they go through `lines` instead of the tiled rasterizer, see `raster_mesh_outline`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PolygonMode {
+    Fill,
+    // the edges of every triangle, `line_width` pixels wide
+    Line,
+    // the vertices of every triangle, as `point_size` pixel squares
+    Point,
+}
+
+// how a new value is compared against the one already stored, passes if `new compare stored`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompareFunction {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunction {
+    pub fn test<T: PartialOrd>(self, new: T, stored: T) -> bool {
+        match self {
+            CompareFunction::Never => false,
+            CompareFunction::Less => new < stored,
+            CompareFunction::Equal => new == stored,
+            CompareFunction::LessEqual => new <= stored,
+            CompareFunction::Greater => new > stored,
+            CompareFunction::NotEqual => new != stored,
+            CompareFunction::GreaterEqual => new >= stored,
+            CompareFunction::Always => true,
+        }
+    }
+
+    // the same test with both sides swapped, Less becomes Greater and so on
+    pub fn mirrored(self) -> Self {
+        match self {
+            CompareFunction::Less => CompareFunction::Greater,
+            CompareFunction::LessEqual => CompareFunction::GreaterEqual,
+            CompareFunction::Greater => CompareFunction::Less,
+            CompareFunction::GreaterEqual => CompareFunction::LessEqual,
+            other => other,
+        }
+    }
+
+    // four lanes at once, for the blocks of the rasterizer
+    pub fn test_lanes(self, new: Vec4, stored: Vec4) -> BVec4A {
+        match self {
+            CompareFunction::Never => BVec4A::FALSE,
+            CompareFunction::Less => new.cmplt(stored),
+            CompareFunction::Equal => new.cmpeq(stored),
+            CompareFunction::LessEqual => new.cmple(stored),
+            CompareFunction::Greater => new.cmpgt(stored),
+            CompareFunction::NotEqual => new.cmpne(stored),
+            CompareFunction::GreaterEqual => new.cmpge(stored),
+            CompareFunction::Always => BVec4A::TRUE,
+        }
+    }
+}
+
+// pushes the depth of a whole triangle, like glPolygonOffset
+// `constant` is added as is, `slope` scales the largest change of depth from one pixel to the next
+// so triangles seen at a grazing angle move further, negative values move towards the camera,
+// positive ones with `DepthRange::Reversed`
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct DepthBias {
+    pub constant: f32,
+    pub slope: f32,
+}
+
+impl DepthBias {
+    // the offset for a triangle at screen positions `sc` with depths `z`
+    pub fn offset(&self, sc: &[Vec2; 3], z: [f32; 3]) -> f32 {
+        if self.slope == 0.0 {
+            return self.constant;
+        }
+        let (d1, d2) = (sc[1] - sc[0], sc[2] - sc[0]);
+        let (z1, z2) = (z[1] - z[0], z[2] - z[0]);
+        let area = d1.perp_dot(d2);
+        if area == 0.0 {
+            return self.constant;
+        }
+        // gradient of the depth plane in pixels
+        let dz_dx = (z1 * d2.y - z2 * d1.y) / area;
+        let dz_dy = (z2 * d1.x - z1 * d2.x) / area;
+        self.constant + self.slope * dz_dx.abs().max(dz_dy.abs())
+    }
+}
+
+// how fragments are tested against and written to the depth attachment
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DepthState {
+    // passes if `fragment depth compare stored depth`, as if smaller depth was closer
+    // framebuffers with `DepthRange::Reversed` mirror it, so Less keeps the closest either way
+    pub compare: CompareFunction,
+    // false keeps the depth attachment as it is, for transparent passes and overlays
+    pub write: bool,
+    pub bias: DepthBias,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self {
+            compare: CompareFunction::Less,
+            write: true,
+            bias: DepthBias::default(),
+        }
+    }
+}
+
+// fixed function state of a single draw call
+#[derive(Debug, Copy, Clone)]
+pub struct PipelineState {
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub polygon_mode: PolygonMode,
+    // in pixels, for lines and points drawn with this state
+    pub line_width: f32,
+    pub point_size: f32,
+    pub depth: DepthState,
+    // None skips the stencil test and leaves the stencil attachment untouched
+    pub stencil: Option<StencilState>,
+    // None overwrites the framebuffer with the fragment color
+    pub blend: Option<BlendState>,
+    // shaded fragments go into the fragment lists of the framebuffer, to be sorted and composited
+    // with alpha blending by `Framebuffer::resolve_fragments`, without lists `blend` is used as usual
+    pub order_independent: bool,
+    // None covers the whole framebuffer
+    pub viewport: Option<Viewport>,
+    // pixels outside of it are left alone, None doesn't cut anything beyond the viewport
+    pub scissor: Option<Tile>,
+}
+
+impl Default for PipelineState {
+    // matches the glTF conventions
+    fn default() -> Self {
+        Self {
+            cull_mode: CullMode::Back,
+            front_face: FrontFace::CounterClockwise,
+            polygon_mode: PolygonMode::Fill,
+            line_width: 1.0,
+            point_size: 1.0,
+            depth: DepthState::default(),
+            stencil: None,
+            blend: None,
+            order_independent: false,
+            viewport: None,
+            scissor: None,
+        }
+    }
+}
+
+impl PipelineState {
+    // the state actually used to draw a mesh with this material, double sided materials are never culled
+    // blended ones keep the depth of what is behind them, unless the pipeline already blends its own way
+    pub fn for_material(&self, material: &Material) -> Self {
+        let mut state = *self;
+        if material.double_sided {
+            state.cull_mode = CullMode::None;
+        }
+        if material.blend && state.blend.is_none() {
+            state.blend = Some(BlendState::ALPHA_BLENDING);
+            state.depth.write = false;
+            state.order_independent = material.order_independent;
+        }
+        state
+    }
+
+    // where normalized device coordinates end up on a framebuffer of `framebuffer_size`
+    pub fn viewport_on(&self, framebuffer_size: Vec2) -> Viewport {
+        self.viewport.unwrap_or(Viewport::full(framebuffer_size))
+    }
+
+    // the pixels drawing with this state may touch, None if there are none
+    pub fn pixel_bounds(&self, framebuffer_size: Vec2) -> Option<Tile> {
+        let rect = self
+            .viewport_on(framebuffer_size)
+            .pixel_rect(framebuffer_size)?;
+        match &self.scissor {
+            Some(scissor) => rect.intersect(scissor),
+            None => Some(rect),
+        }
+    }
+
+    // the color a fragment leaves in a framebuffer sample that held `dst`
+    pub fn blend_color(&self, src: u32, dst: u32) -> u32 {
+        match &self.blend {
+            Some(blend) => blend.blend(src, dst),
+            None => src,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use glam::Vec2;
+
+    fn depth_tested(depth: DepthState) -> PipelineState {
+        PipelineState {
+            cull_mode: CullMode::None,
+            depth,
+            ..Default::default()
+        }
+    }
+
+    // draws `mesh` over a cleared color attachment and tells if every pixel got drawn
+    fn draws_everywhere(mesh: &Mesh, depth: DepthState, framebuffer: &mut Framebuffer) -> bool {
+        framebuffer.clear_color(0);
+        draw(mesh, WHITE, &depth_tested(depth), framebuffer);
+        framebuffer.color().iter().all(|&c| c != 0)
+    }
+
+    #[test]
+    fn compare_and_write() {
+        for samples in [1, 4] {
+            let mut framebuffer =
+                Framebuffer::with_samples(64, 64, SamplePattern::standard(samples));
+            framebuffer.clear(0);
+            draw(
+                &quad(-1.0, 1.0, 0.2),
+                WHITE,
+                &depth_tested(DepthState::default()),
+                &mut framebuffer,
+            );
+            let near = framebuffer.depth().to_vec();
+
+            let behind = quad(-1.0, 1.0, 0.6);
+            assert!(!draws_everywhere(
+                &behind,
+                DepthState::default(),
+                &mut framebuffer
+            ));
+            let greater = DepthState {
+                compare: CompareFunction::Greater,
+                write: false,
+                ..Default::default()
+            };
+            assert!(draws_everywhere(&behind, greater, &mut framebuffer));
+            assert_eq!(framebuffer.depth(), &near[..]);
+
+            let never = DepthState {
+                compare: CompareFunction::Never,
+                ..Default::default()
+            };
+            framebuffer.clear_color(0);
+            draw(
+                &quad(-1.0, 1.0, 0.0),
+                WHITE,
+                &depth_tested(never),
+                &mut framebuffer,
+            );
+            assert!(framebuffer.color().iter().all(|&c| c == 0));
+        }
+    }
+
+    #[test]
+    fn blended_materials() {
+        let material = Material {
+            blend: true,
+            ..Default::default()
+        };
+        let state = PipelineState::default().for_material(&material);
+        assert_eq!(state.blend, Some(BlendState::ALPHA_BLENDING));
+        assert!(!state.depth.write);
+
+        // a pipeline that already blends is left alone
+        let additive = PipelineState {
+            blend: Some(BlendState::ADDITIVE),
+            ..Default::default()
+        };
+        let state = additive.for_material(&material);
+        assert_eq!(state.blend, Some(BlendState::ADDITIVE));
+        assert!(state.depth.write);
+
+        // and drawing the mesh blends it without the pipeline asking for it
+        let mut framebuffer = Framebuffer::new(16, 16);
+        framebuffer.clear(from_u8_argb(255, 0, 0, 0));
+        let mut mesh = quad(-1.0, 1.0, 0.5);
+        mesh.material = material;
+        let half_red = from_u8_argb(128, 255, 0, 0);
+        draw(
+            &mesh,
+            half_red,
+            &depth_tested(DepthState::default()),
+            &mut framebuffer,
+        );
+        assert_eq!(
+            framebuffer.color_at(8, 8),
+            Some(BlendState::ALPHA_BLENDING.blend(half_red, from_u8_argb(255, 0, 0, 0)))
+        );
+    }
+
+    #[test]
+    fn equal_depth_passes_less_equal() {
+        // the second draw is entirely at the depth of the first, which hi-z must not cull
+        let mut framebuffer = Framebuffer::new(128, 128);
+        framebuffer.clear(0);
+        let mesh = quad(-1.0, 1.0, 0.5);
+        draw(
+            &mesh,
+            WHITE,
+            &depth_tested(DepthState::default()),
+            &mut framebuffer,
+        );
+        assert!(!draws_everywhere(
+            &mesh,
+            DepthState::default(),
+            &mut framebuffer
+        ));
+        let less_equal = DepthState {
+            compare: CompareFunction::LessEqual,
+            ..Default::default()
+        };
+        assert!(draws_everywhere(&mesh, less_equal, &mut framebuffer));
+    }
+
+    #[test]
+    fn bias_pulls_decals_in_front() {
+        let mut framebuffer = Framebuffer::new(64, 64);
+        framebuffer.clear(0);
+        // a tilted plane, so the slope term matters
+        let mut mesh = quad(-1.0, 1.0, 0.5);
+        for v in &mut mesh.vertices {
+            v.pos.z += v.pos.x * 0.3;
+        }
+        draw(
+            &mesh,
+            WHITE,
+            &depth_tested(DepthState::default()),
+            &mut framebuffer,
+        );
+        let biased = DepthState {
+            bias: DepthBias {
+                constant: -1e-5,
+                slope: -1.0,
+            },
+            ..Default::default()
+        };
+        assert!(draws_everywhere(&mesh, biased, &mut framebuffer));
+
+        let bias = DepthBias {
+            constant: 0.01,
+            slope: 2.0,
+        };
+        let sc = [Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::new(0.0, 10.0)];
+        assert_eq!(bias.offset(&sc, [0.5, 0.5, 0.5]), 0.01);
+        assert!((bias.offset(&sc, [0.5, 0.6, 0.5]) - 0.03).abs() < 1e-6);
+    }
+}
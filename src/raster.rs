@@ -0,0 +1,274 @@
+use glam::{Vec2, Vec4};
+
+// screen positions are snapped to a 1/256 pixel grid before rasterization,
+// so edge functions are evaluated exactly with integer math
+pub const SUBPIXEL_BITS: u32 = 8;
+pub const SUBPIXEL_SCALE: i64 = 1 << SUBPIXEL_BITS;
+// pixel centers sit half a pixel into the subpixel grid
+pub const SUBPIXEL_HALF: i64 = SUBPIXEL_SCALE / 2;
+
+// the largest window coordinate, in pixels, the edge setup handles
+// edge values are at most about 8 * coord^2 in subpixel units, which has to stay below i64::MAX
+pub const MAX_WINDOW_COORD: f32 = (1u64 << (30 - SUBPIXEL_BITS)) as f32;
+
+// pixels are shaded in horizontal blocks of this many, one per SIMD lane
+pub const LANES: usize = 4;
+pub const LANE_OFFSETS: Vec4 = Vec4::new(0.0, 1.0, 2.0, 3.0);
+
+pub fn to_fixed(v: f32) -> i64 {
+    (v * SUBPIXEL_SCALE as f32).round() as i64
+}
+
+pub fn to_fixed_vec(v: Vec2) -> (i64, i64) {
+    (to_fixed(v.x), to_fixed(v.y))
+}
+
+// subpixel coordinates of the center of pixel (x, y)
+pub fn pixel_center_fixed(x: usize, y: usize) -> (i64, i64) {
+    (
+        ((x as i64) << SUBPIXEL_BITS) + SUBPIXEL_HALF,
+        ((y as i64) << SUBPIXEL_BITS) + SUBPIXEL_HALF,
+    )
+}
+
+// e(p) = a * p.x + b * p.y + c, same sign convention as `edge_function(p, v0, v1)`
+// `bias` is 0 for top-left edges and -1 for the others, so a sample exactly on an edge
+// shared by two triangles only counts for one of them
+#[derive(Debug, Copy, Clone)]
+pub struct EdgeFunction {
+    pub a: i64,
+    pub b: i64,
+    pub c: i64,
+    pub bias: i64,
+}
+
+impl EdgeFunction {
+    pub fn new(v0: (i64, i64), v1: (i64, i64)) -> Self {
+        let a = v0.1 - v1.1;
+        let b = v1.0 - v0.0;
+        let c = -(a * v0.0 + b * v0.1);
+        let mut edge = Self { a, b, c, bias: 0 };
+        edge.bias = if edge.is_top_left() { 0 } else { -1 };
+        edge
+    }
+
+    pub fn flipped(&self) -> Self {
+        let mut edge = Self {
+            a: -self.a,
+            b: -self.b,
+            c: -self.c,
+            bias: 0,
+        };
+        edge.bias = if edge.is_top_left() { 0 } else { -1 };
+        edge
+    }
+
+    // with the inside of the triangle on the positive side and y pointing down the rows,
+    // a left edge has the inside to its right (e grows with x)
+    // and a top edge is horizontal with the inside below it (e grows with y)
+    pub fn is_top_left(&self) -> bool {
+        self.a > 0 || (self.a == 0 && self.b > 0)
+    }
+
+    pub fn eval(&self, p: (i64, i64)) -> i64 {
+        self.a * p.0 + self.b * p.1 + self.c
+    }
+
+    pub fn covers(value: i64, bias: i64) -> bool {
+        value + bias >= 0
+    }
+}
+
+// the three edges of a snapped triangle, oriented so the inside is positive
+// edge i is the one opposite to vertex i, so its value is the unnormalized barycentric weight of vertex i
+#[derive(Debug, Copy, Clone)]
+pub struct TriangleEdges {
+    pub edges: [EdgeFunction; 3],
+    // twice the signed area in subpixel units, before the orientation fix
+    pub signed_area: i64,
+    pub inv_area: f32,
+}
+
+impl TriangleEdges {
+    pub fn new(sc: &[Vec2; 3]) -> Self {
+        let v = [
+            to_fixed_vec(sc[0]),
+            to_fixed_vec(sc[1]),
+            to_fixed_vec(sc[2]),
+        ];
+        let mut edges = [
+            EdgeFunction::new(v[1], v[2]),
+            EdgeFunction::new(v[2], v[0]),
+            EdgeFunction::new(v[0], v[1]),
+        ];
+        let signed_area = edges[0].eval(v[0]);
+        if signed_area < 0 {
+            edges = edges.map(|edge| edge.flipped());
+        }
+        Self {
+            edges,
+            signed_area,
+            inv_area: 1.0 / signed_area.abs() as f32,
+        }
+    }
+
+    pub fn eval(&self, p: (i64, i64)) -> [i64; 3] {
+        self.edges.map(|edge| edge.eval(p))
+    }
+
+    // how much the edge values change when moving one whole pixel right or down
+    pub fn step_x(&self) -> [i64; 3] {
+        self.edges.map(|edge| edge.a * SUBPIXEL_SCALE)
+    }
+
+    pub fn step_y(&self) -> [i64; 3] {
+        self.edges.map(|edge| edge.b * SUBPIXEL_SCALE)
+    }
+
+    pub fn covers(&self, e: [i64; 3]) -> bool {
+        EdgeFunction::covers(e[0], self.edges[0].bias)
+            && EdgeFunction::covers(e[1], self.edges[1].bias)
+            && EdgeFunction::covers(e[2], self.edges[2].bias)
+    }
+
+    pub fn barycentric_from_edges(&self, e: [i64; 3]) -> glam::Vec3 {
+        glam::vec3(
+            e[0] as f32 * self.inv_area,
+            e[1] as f32 * self.inv_area,
+            e[2] as f32 * self.inv_area,
+        )
+    }
+
+    // barycentric coordinates of the sample, if the fill rule says it is covered
+    pub fn barycentric(&self, p: (i64, i64)) -> Option<glam::Vec3> {
+        let e = self.eval(p);
+        self.covers(e).then(|| self.barycentric_from_edges(e))
+    }
+
+    // the pixels of a row that are inside all three edges, as offsets from the pixel `e_row` was evaluated at
+    // every edge is a line, so the covered pixels of a row are always one contiguous span
+    // returns None when the row misses the triangle completely
+    pub fn row_span(&self, e_row: [i64; 3], width: usize) -> Option<(usize, usize)> {
+        let mut first = 0;
+        let mut last = width as i64 - 1;
+        for (edge, e) in self.edges.iter().zip(e_row) {
+            // need e + step * x + bias >= 0
+            let value = e + edge.bias;
+            let step = edge.a * SUBPIXEL_SCALE;
+            if step > 0 {
+                // x >= ceil(-value / step)
+                first =
+                    first.max((-value).div_euclid(step) + ((-value).rem_euclid(step) != 0) as i64);
+            } else if step < 0 {
+                // x <= floor(value / -step)
+                last = last.min(value.div_euclid(-step));
+            } else if value < 0 {
+                return None;
+            }
+        }
+        (first <= last).then_some((first as usize, last as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{UVec3, Vec2, Vec3, Vec4};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const SIZE: usize = 64;
+
+    struct CoverageShader;
+
+    // counts every fragment per pixel, fragments are discarded so nothing is depth rejected
+    impl VertexShader<Vec<AtomicU32>> for CoverageShader {
+        type Varyings = ();
+
+        fn vertex(&self, vertex: &Vertex, _: &Vec<AtomicU32>) -> ClipVertex<()> {
+            ClipVertex::new(vertex.pos, ())
+        }
+    }
+
+    impl FragmentShader<Vec<AtomicU32>, ()> for CoverageShader {
+        fn fragment(&self, frag_coord: Vec4, _: &(), counts: &Vec<AtomicU32>) -> Option<u32> {
+            let id = coords_to_index(frag_coord.x as usize, frag_coord.y as usize, SIZE);
+            counts[id].fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+
+    // grid of quads spanning the whole viewport, inner vertices moved around by `offset`
+    fn grid(cells: usize, offset: impl Fn(usize, usize) -> Vec2) -> Mesh {
+        let mut mesh = Mesh::new();
+        for j in 0..=cells {
+            for i in 0..=cells {
+                let mut p = glam::vec2(i as f32, j as f32) * (SIZE / cells) as f32;
+                if i != 0 && j != 0 && i != cells && j != cells {
+                    p += offset(i, j);
+                }
+                // pixel position to ndc
+                let ndc = p / SIZE as f32 * 2.0 - 1.0;
+                mesh.vertices.push(Vertex::new(
+                    Vec4::new(ndc.x, ndc.y, 0.5, 1.0),
+                    Vec3::Z,
+                    Vec3::ONE,
+                    Vec2::ZERO,
+                ));
+            }
+        }
+        let id = |i: usize, j: usize| (i + j * (cells + 1)) as u32;
+        for j in 0..cells {
+            for i in 0..cells {
+                let (a, b, c, d) = (id(i, j), id(i + 1, j), id(i, j + 1), id(i + 1, j + 1));
+                mesh.triangle_indices.push(UVec3::new(a, b, c));
+                mesh.triangle_indices.push(UVec3::new(b, d, c));
+            }
+        }
+        mesh
+    }
+
+    fn coverage(mesh: &Mesh) -> Vec<u32> {
+        let counts: Vec<AtomicU32> = (0..SIZE * SIZE).map(|_| AtomicU32::new(0)).collect();
+        let mut framebuffer = Framebuffer::new(SIZE, SIZE);
+        raster_mesh(
+            mesh,
+            &CoverageShader,
+            &CoverageShader,
+            &counts,
+            &PipelineState {
+                cull_mode: CullMode::None,
+                ..Default::default()
+            },
+            &RenderSettings::default(),
+            &mut framebuffer,
+        );
+        counts.into_iter().map(|c| c.into_inner()).collect()
+    }
+
+    #[test]
+    fn shared_edges_cover_every_pixel_once() {
+        // vertices on pixel centers put samples exactly on the edges
+        let aligned = grid(8, |_, _| Vec2::splat(0.5));
+        assert!(coverage(&aligned).iter().all(|&c| c == 1));
+
+        // some pseudo random subpixel jitter
+        let jittered = grid(16, |i, j| {
+            let hash = (i * 7919 + j * 104729) % 1000;
+            glam::vec2(
+                hash as f32 / 500.0 - 1.0,
+                (hash * 31 % 1000) as f32 / 500.0 - 1.0,
+            )
+        });
+        assert!(coverage(&jittered).iter().all(|&c| c == 1));
+    }
+
+    #[test]
+    fn top_left_rule() {
+        // edge going down the rows with the inside on the right is a left edge
+        assert!(EdgeFunction::new((0, 0), (0, 10)).flipped().is_top_left());
+        assert!(!EdgeFunction::new((0, 0), (0, 10)).is_top_left());
+        // horizontal edge with the inside below is a top edge
+        assert!(EdgeFunction::new((10, 0), (0, 0)).flipped().is_top_left());
+        assert!(!EdgeFunction::new((10, 0), (0, 0)).is_top_left());
+    }
+}
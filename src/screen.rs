@@ -0,0 +1,291 @@
+use crate::{
+    from_u8_argb, raster_mesh, Camera2D, ClipVertex, CullMode, FragmentShader, Framebuffer, Mesh,
+    PipelineState, RasterStats, RenderSettings, Texture, Vertex, VertexShader,
+};
+use glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+
+pub struct ScreenUniforms<'a> {
+    pub projection: Mat4,
+    pub texture: Option<&'a Texture>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ScreenVaryings {
+    pub color: Vec3,
+    pub uv: Vec2,
+}
+
+crate::impl_varying!(ScreenVaryings { color, uv });
+
+// sprites and text, unlit, the texture multiplied with the vertex color
+// x and y of a vertex are its world position, z its layer and w a perspective weight:
+// at 1 everywhere the varyings are interpolated linearly across the screen, a quad distorted into
+// a trapezoid gets a correct looking texture by giving the vertices of the far side a larger w
+// texels with an alpha of 0 are discarded, so they are skipped even without blending
+pub struct ScreenShader;
+
+impl<'a> VertexShader<ScreenUniforms<'a>> for ScreenShader {
+    type Varyings = ScreenVaryings;
+
+    fn vertex(&self, vertex: &Vertex, uniforms: &ScreenUniforms<'a>) -> ClipVertex<ScreenVaryings> {
+        ClipVertex::new(
+            uniforms.projection * vertex.pos.xyz().extend(1.0) * vertex.pos.w,
+            ScreenVaryings {
+                color: vertex.color,
+                uv: vertex.uv,
+            },
+        )
+    }
+}
+
+impl<'a> FragmentShader<ScreenUniforms<'a>, ScreenVaryings> for ScreenShader {
+    fn fragment(
+        &self,
+        _frag_coord: Vec4,
+        varyings: &ScreenVaryings,
+        uniforms: &ScreenUniforms<'a>,
+    ) -> Option<u32> {
+        let color = varyings.color;
+        match uniforms.texture {
+            Some(texture) => {
+                let tex_color = texture.rgb_at_uv(varyings.uv.x, varyings.uv.y);
+                let a = (tex_color >> 24) as u8;
+                let r = (tex_color >> 16) as u8;
+                let g = (tex_color >> 8) as u8;
+                let b = tex_color as u8;
+                if a == 0 {
+                    return None;
+                }
+                Some(from_u8_argb(
+                    a,
+                    (r as f32 * color.x) as u8,
+                    (g as f32 * color.y) as u8,
+                    (b as f32 * color.z) as u8,
+                ))
+            }
+            None => Some(from_u8_argb(
+                255,
+                (color.x * 255.0) as u8,
+                (color.y * 255.0) as u8,
+                (color.z * 255.0) as u8,
+            )),
+        }
+    }
+}
+
+// a mesh in the world of `camera`, through the same rasterizer as 3D meshes
+// 2D meshes are never culled, whichever way their triangles wind
+pub fn raster_mesh_2d(
+    mesh: &Mesh,
+    texture: Option<&Texture>,
+    camera: &Camera2D,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) -> RasterStats {
+    let pipeline = PipelineState {
+        cull_mode: CullMode::None,
+        ..*pipeline
+    };
+    let uniforms = ScreenUniforms {
+        projection: camera.projection(),
+        texture,
+    };
+    raster_mesh(
+        mesh,
+        &ScreenShader,
+        &ScreenShader,
+        &uniforms,
+        &pipeline,
+        &RenderSettings::default(),
+        framebuffer,
+    )
+}
+
+// draws many small meshes like sprites or glyphs in as few draws as possible, neighbouring meshes
+// with the same material are merged into one
+// when any of them blends they are drawn back to front by the lowest layer of their vertices,
+// the ones on the same layer in the order given, with one draw per layer and material,
+// otherwise the depth test sorts them and they stay in the order given
+pub fn raster_layers_2d(
+    meshes: &[Mesh],
+    texture: Option<&Texture>,
+    camera: &Camera2D,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) -> RasterStats {
+    let layer = |mesh: &Mesh| {
+        mesh.vertices
+            .iter()
+            .map(|vertex| vertex.pos.z)
+            .fold(f32::INFINITY, f32::min)
+    };
+    let sorted = meshes
+        .iter()
+        .any(|mesh| pipeline.for_material(&mesh.material).blend.is_some());
+    let mut order: Vec<&Mesh> = meshes.iter().collect();
+    if sorted {
+        order.sort_by(|a, b| layer(a).total_cmp(&layer(b)));
+    }
+
+    let mut stats = RasterStats::default();
+    for batch in order.chunk_by(|a, b| {
+        a.material == b.material && (!sorted || layer(a).total_cmp(&layer(b)).is_eq())
+    }) {
+        let mut mesh = Mesh {
+            material: batch[0].material,
+            ..Default::default()
+        };
+        for part in batch {
+            mesh.append(part);
+        }
+        stats += raster_mesh_2d(&mesh, texture, camera, pipeline, framebuffer);
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{UVec3, Vec2, Vec3, Vec4};
+
+    fn sprite(min: Vec2, max: Vec2, layer: f32, color: Vec3) -> Mesh {
+        let vertex =
+            |x: f32, y: f32| Vertex::new(Vec4::new(x, y, layer, 1.0), Vec3::Z, color, Vec2::ZERO);
+        Mesh {
+            vertices: vec![
+                vertex(min.x, min.y),
+                vertex(max.x, min.y),
+                vertex(min.x, max.y),
+                vertex(max.x, max.y),
+            ],
+            triangle_indices: vec![UVec3::new(0, 1, 2), UVec3::new(1, 3, 2)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn camera_2d() {
+        let camera = Camera2D {
+            position: Vec2::new(100.0, 50.0),
+            zoom: 2.0,
+            ..Camera2D::new(Vec2::new(64.0, 32.0))
+        };
+        let ndc = camera
+            .projection()
+            .project_point3(Vec3::new(110.0, 60.0, camera.layers));
+        let window = Viewport::full(camera.size).to_window(ndc.truncate());
+        assert!((window - Vec2::new(20.0, 20.0)).length() < 1e-4);
+        assert!(ndc.z.abs() < 1e-6);
+        let back = camera.projection().project_point3(Vec3::new(0.0, 0.0, 0.0));
+        assert!((back.z - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn layers_sort_back_to_front() {
+        let mut framebuffer = Framebuffer::new(32, 32);
+        let camera = Camera2D::new(framebuffer.size());
+        let front = sprite(Vec2::new(0.0, 0.0), Vec2::new(16.0, 16.0), 2.0, Vec3::X);
+        let back = sprite(Vec2::new(8.0, 8.0), Vec2::new(24.0, 24.0), 1.0, Vec3::Y);
+
+        // opaque ones are sorted by the depth test, whatever order they are drawn in,
+        // so they are drawn together without sorting
+        let pipeline = PipelineState::default();
+        for meshes in [[&front, &back], [&back, &front]] {
+            framebuffer.clear(0);
+            for mesh in meshes {
+                raster_mesh_2d(mesh, None, &camera, &pipeline, &mut framebuffer);
+            }
+            assert_eq!(framebuffer.color_at(12, 12), Some(0xffff0000));
+            assert_eq!(framebuffer.color_at(20, 20), Some(0xff00ff00));
+            let separate = framebuffer.color().to_vec();
+
+            framebuffer.clear(0);
+            raster_layers_2d(
+                &meshes.map(Mesh::clone),
+                None,
+                &camera,
+                &pipeline,
+                &mut framebuffer,
+            );
+            assert_eq!(framebuffer.color(), separate);
+        }
+
+        // blended ones have to be drawn back to front, the front one is listed first here
+        framebuffer.clear(0);
+        let pipeline = PipelineState {
+            depth: DepthState {
+                write: false,
+                ..Default::default()
+            },
+            blend: Some(BlendState::ALPHA_BLENDING),
+            ..Default::default()
+        };
+        let stats = raster_layers_2d(&[front, back], None, &camera, &pipeline, &mut framebuffer);
+        assert_eq!(stats.triangles, 4);
+        assert_eq!(framebuffer.color_at(12, 12), Some(0xffff0000));
+        assert_eq!(framebuffer.color_at(20, 20), Some(0xff00ff00));
+    }
+
+    #[test]
+    fn sub_pixel_positions() {
+        let mut framebuffer = Framebuffer::new(32, 8);
+        let camera = Camera2D::new(framebuffer.size());
+        for (offset, first) in [(0.25, 10), (0.75, 11)] {
+            framebuffer.clear(0);
+            let x = 10.0 + offset;
+            let mesh = sprite(Vec2::new(x, 0.0), Vec2::new(x + 10.0, 8.0), 0.0, Vec3::ONE);
+            raster_mesh_2d(
+                &mesh,
+                None,
+                &camera,
+                &PipelineState::default(),
+                &mut framebuffer,
+            );
+            let lit: Vec<usize> = (0..32)
+                .filter(|&x| framebuffer.color_at(x, 4) != Some(0))
+                .collect();
+            assert_eq!(lit, (first..first + 10).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn perspective_weights() {
+        let mut framebuffer = Framebuffer::new(64, 8);
+        let camera = Camera2D::new(framebuffer.size());
+        // black on the left, red on the right, the right side three times as far away
+        let mut mesh = sprite(Vec2::ZERO, Vec2::new(64.0, 8.0), 0.0, Vec3::ZERO);
+        for id in [1, 3] {
+            mesh.vertices[id].color = Vec3::X;
+        }
+        let red =
+            |framebuffer: &Framebuffer, x: usize| (framebuffer.color_at(x, 4).unwrap() >> 16) as u8;
+
+        framebuffer.clear(0);
+        raster_mesh_2d(
+            &mesh,
+            None,
+            &camera,
+            &PipelineState::default(),
+            &mut framebuffer,
+        );
+        // linear across the screen
+        assert_eq!(red(&framebuffer, 32), (32.5 / 64.0 * 255.0) as u8);
+
+        for id in [1, 3] {
+            mesh.vertices[id].pos.w = 3.0;
+        }
+        framebuffer.clear(0);
+        raster_mesh_2d(
+            &mesh,
+            None,
+            &camera,
+            &PipelineState::default(),
+            &mut framebuffer,
+        );
+        // halfway across the screen is only a quarter of the way along the quad
+        let s: f32 = 32.5 / 64.0;
+        let t = (s / 3.0) / (1.0 - s + s / 3.0);
+        assert!(red(&framebuffer, 32).abs_diff((t * 255.0) as u8) <= 1);
+        assert!(red(&framebuffer, 32) < 80);
+    }
+}
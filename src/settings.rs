@@ -0,0 +1,42 @@
+use crate::{SamplePattern, Viewport, MAX_WINDOW_COORD};
+
+// frame wide knobs of the rasterizer, as opposed to the per draw shaders and uniforms
+#[derive(Debug, Copy, Clone)]
+pub struct RenderSettings {
+    // how far outside the viewport triangles are left to the rasterizer instead of being clipped,
+    // as a multiple of the clip space w, 1.0 clips exactly at the viewport edges
+    // drawing uses it through `guard_band_for`, which keeps it in the range that works
+    pub guard_band: f32,
+    // debug mode, triangles produced by clipping are drawn in flat red, green and blue
+    // instead of being shaded, so it is easy to see where and how the clipper cut the mesh
+    pub clip_visualization: bool,
+    // multisample anti-aliasing, the frame has to be drawn into a `Framebuffer` with this pattern
+    // and resolved into the presented one, None draws a single sample at each pixel center
+    pub msaa: Option<SamplePattern>,
+    // post process anti-aliasing over the finished 3D image, applied with `fxaa` before any overlay
+    pub fxaa: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            guard_band: 1.0,
+            clip_visualization: false,
+            msaa: None,
+            fxaa: false,
+        }
+    }
+}
+
+impl RenderSettings {
+    // the guard band clamped to at least 1, less would clip inside the viewport,
+    // and to at most what keeps the window coordinates of `viewport` in the fixed point range
+    pub fn guard_band_for(&self, viewport: &Viewport) -> f32 {
+        // window = offset + (ndc + 1) / 2 * size with |ndc| <= guard band
+        let extent = (viewport.offset.abs() + viewport.size.abs())
+            .max_element()
+            .max(1.0);
+        let max = (MAX_WINDOW_COORD / extent - 1.0).max(1.0);
+        self.guard_band.clamp(1.0, max)
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{from_u8_argb, ClipVertex, Texture, Varying, VaryingBlock, Vertex, LANES};
+use glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+
+// `U` is the uniform block shared by both stages of a draw call,
+// anything the shaders need that is constant for the whole mesh goes in there
+
+// runs once per mesh vertex, has to return the position in clip space
+// plus the varyings that get clipped and interpolated for the fragment shader
+pub trait VertexShader<U> {
+    type Varyings: Varying;
+
+    fn vertex(&self, vertex: &Vertex, uniforms: &U) -> ClipVertex<Self::Varyings>;
+}
+
+// runs once per covered pixel that passes the depth test
+// `frag_coord` is the window space fragment coordinate (x, y, depth, 1/w)
+// `varyings` are the perspective correct interpolated outputs of the vertex shader
+// returns the ARGB color to write, or None to discard the fragment
+pub trait FragmentShader<U, V> {
+    fn fragment(&self, frag_coord: Vec4, varyings: &V, uniforms: &U) -> Option<u32>;
+
+    // shades a horizontal block of LANES neighbouring pixels, only the lanes set in `mask` are used
+    // override it to shade several pixels at once with SIMD, `varyings.lanes` interpolates a varying
+    // for the whole block in one Vec4, by default every lane goes through `fragment`
+    fn fragment_block(
+        &self,
+        frag_coords: &[Vec4; LANES],
+        varyings: &VaryingBlock<V>,
+        mask: u32,
+        uniforms: &U,
+    ) -> [Option<u32>; LANES]
+    where
+        V: Varying,
+    {
+        std::array::from_fn(|k| {
+            if mask & (1 << k) != 0 {
+                self.fragment(frag_coords[k], &varyings.lane(k), uniforms)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+pub struct DefaultUniforms<'a> {
+    pub mvp: Mat4,
+    pub model_matrix: Mat4,
+    // inverse transpose of the model matrix, to get normals into world space
+    pub normal_matrix: Mat4,
+    pub light_dir: Vec3,
+    pub ambient: Vec3,
+    pub texture: Option<&'a Texture>,
+}
+
+impl<'a> DefaultUniforms<'a> {
+    pub fn new(mvp: Mat4, model_matrix: Mat4, texture: Option<&'a Texture>) -> Self {
+        Self {
+            mvp,
+            model_matrix,
+            normal_matrix: Mat4::transpose(&Mat4::inverse(&model_matrix)),
+            light_dir: Vec3::ONE.normalize(), // light in the position (1, 1, 1)
+            ambient: glam::vec3(0.3, 0.3, 0.3), // fixed amount of ambient light
+            texture,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct DefaultVaryings {
+    pub normal: Vec3,
+    pub color: Vec3,
+    pub uv: Vec2,
+}
+
+crate::impl_varying!(DefaultVaryings { normal, color, uv });
+
+// single directional light plus ambient, multiplied with the texture if there is one
+// the alpha is the one of the texture, or opaque without one
+pub struct DefaultShader;
+
+impl<'a> VertexShader<DefaultUniforms<'a>> for DefaultShader {
+    type Varyings = DefaultVaryings;
+
+    fn vertex(
+        &self,
+        vertex: &Vertex,
+        uniforms: &DefaultUniforms<'a>,
+    ) -> ClipVertex<DefaultVaryings> {
+        ClipVertex::new(
+            uniforms.mvp * vertex.pos.xyz().extend(1.0),
+            DefaultVaryings {
+                normal: (uniforms.normal_matrix * vertex.normal.extend(0.0)).xyz(),
+                color: vertex.color,
+                uv: vertex.uv,
+            },
+        )
+    }
+}
+
+impl<'a> FragmentShader<DefaultUniforms<'a>, DefaultVaryings> for DefaultShader {
+    fn fragment(
+        &self,
+        _frag_coord: Vec4,
+        varyings: &DefaultVaryings,
+        uniforms: &DefaultUniforms<'a>,
+    ) -> Option<u32> {
+        let n_dot_l = varyings.normal.dot(uniforms.light_dir);
+        let color = varyings.color * n_dot_l + uniforms.ambient;
+        match uniforms.texture {
+            Some(texture) => {
+                let tex_color = texture.rgb_at_uv(varyings.uv.x, varyings.uv.y);
+                let a = (tex_color >> 24) as u8;
+                let r = (tex_color >> 16) as u8;
+                let g = (tex_color >> 8) as u8;
+                let b = tex_color as u8;
+                Some(from_u8_argb(
+                    a,
+                    (r as f32 * color.x) as u8,
+                    (g as f32 * color.y) as u8,
+                    (b as f32 * color.z) as u8,
+                ))
+            }
+            None => Some(from_u8_argb(
+                255,
+                (color.x * 255.0) as u8,
+                (color.y * 255.0) as u8,
+                (color.z * 255.0) as u8,
+            )),
+        }
+    }
+
+    // same math as `fragment`, with the lighting of the whole block done one channel per Vec4
+    fn fragment_block(
+        &self,
+        _frag_coords: &[Vec4; LANES],
+        varyings: &VaryingBlock<DefaultVaryings>,
+        mask: u32,
+        uniforms: &DefaultUniforms<'a>,
+    ) -> [Option<u32>; LANES] {
+        let light = uniforms.light_dir;
+        let n_dot_l = varyings.lanes(|v| v.normal.x) * light.x
+            + varyings.lanes(|v| v.normal.y) * light.y
+            + varyings.lanes(|v| v.normal.z) * light.z;
+        let mut r = varyings.lanes(|v| v.color.x) * n_dot_l + uniforms.ambient.x;
+        let mut g = varyings.lanes(|v| v.color.y) * n_dot_l + uniforms.ambient.y;
+        let mut b = varyings.lanes(|v| v.color.z) * n_dot_l + uniforms.ambient.z;
+        let mut a = Vec4::splat(255.0);
+        match uniforms.texture {
+            Some(texture) => {
+                let (u, v) = (varyings.lanes(|v| v.uv.x), varyings.lanes(|v| v.uv.y));
+                let texels: [u32; LANES] = std::array::from_fn(|k| {
+                    if mask & (1 << k) != 0 {
+                        texture.rgb_at_uv(u[k], v[k])
+                    } else {
+                        0
+                    }
+                });
+                let channel =
+                    |shift: u32| Vec4::from_array(texels.map(|t| (t >> shift) as u8 as f32));
+                r *= channel(16);
+                g *= channel(8);
+                b *= channel(0);
+                a = channel(24);
+            }
+            None => {
+                r *= 255.0;
+                g *= 255.0;
+                b *= 255.0;
+            }
+        }
+        std::array::from_fn(|k| {
+            (mask & (1 << k) != 0)
+                .then(|| from_u8_argb(a[k] as u8, r[k] as u8, g[k] as u8, b[k] as u8))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{UVec3, Vec2, Vec3, Vec4};
+
+    struct OffsetUniforms {
+        offset: Vec4,
+        color: u32,
+    }
+
+    // unlike the flat shader of the other tests, this one uses its uniforms in both stages
+    struct OffsetShader;
+
+    impl VertexShader<OffsetUniforms> for OffsetShader {
+        type Varyings = ();
+
+        fn vertex(&self, vertex: &Vertex, uniforms: &OffsetUniforms) -> ClipVertex<()> {
+            ClipVertex::new(vertex.pos + uniforms.offset, ())
+        }
+    }
+
+    impl FragmentShader<OffsetUniforms, ()> for OffsetShader {
+        fn fragment(&self, frag_coord: Vec4, _: &(), uniforms: &OffsetUniforms) -> Option<u32> {
+            // discard the left half of the screen
+            (frag_coord.x >= 8.0).then_some(uniforms.color)
+        }
+    }
+
+    #[test]
+    fn custom_shaders() {
+        let vertex =
+            |x: f32, y: f32| Vertex::new(Vec4::new(x, y, 0.5, 1.0), Vec3::Z, Vec3::ONE, Vec2::ZERO);
+        let quad = Mesh {
+            triangle_indices: vec![UVec3::new(0, 2, 1), UVec3::new(2, 3, 1)],
+            vertices: vec![
+                vertex(-2.0, -0.5),
+                vertex(-2.0, 0.5),
+                vertex(0.0, -0.5),
+                vertex(0.0, 0.5),
+            ],
+            ..Default::default()
+        };
+        let uniforms = OffsetUniforms {
+            // moves the quad from the left half of clip space to span the whole width
+            offset: Vec4::new(1.0, 0.0, 0.0, 0.0),
+            color: from_u8_rgb(255, 0, 0),
+        };
+        let mut framebuffer = Framebuffer::new(16, 16);
+        raster_mesh(
+            &quad,
+            &OffsetShader,
+            &OffsetShader,
+            &uniforms,
+            &PipelineState::default(),
+            &RenderSettings::default(),
+            &mut framebuffer,
+        );
+
+        for (id, &color) in framebuffer.color().iter().enumerate() {
+            let (x, y) = index_to_coords(id, 16);
+            let covered = x >= 8 && (4..12).contains(&y);
+            assert_eq!(color, if covered { uniforms.color } else { 0 });
+        }
+    }
+
+    #[test]
+    fn block_matches_single_fragments() {
+        let texture = Texture::load(std::path::Path::new("assets/textures/bee_icon_256.png"));
+        let pv: [DefaultVaryings; 3] = std::array::from_fn(|i| DefaultVaryings {
+            normal: Vec3::new(i as f32 - 1.5, 1.0, 0.5).normalize(),
+            color: Vec3::new(1.0, 0.5, 0.25 * i as f32),
+            uv: Vec2::new(0.2 + 0.3 * i as f32, 0.7 - 0.2 * i as f32),
+        });
+        // far apart, like a minified texture, both entry points still sample the same texels
+        let varyings = VaryingBlock::new(
+            &pv,
+            [
+                Vec4::new(1.0, 0.6, 0.2, 0.1),
+                Vec4::new(0.0, 0.3, 0.5, 0.2),
+                Vec4::new(0.0, 0.1, 0.3, 0.7),
+            ],
+        );
+        let frag_coords = [Vec4::ZERO; LANES];
+        for texture in [None, Some(&texture)] {
+            let uniforms =
+                DefaultUniforms::new(glam::Mat4::IDENTITY, glam::Mat4::IDENTITY, texture);
+            let block = DefaultShader.fragment_block(&frag_coords, &varyings, 0b1011, &uniforms);
+            for k in 0..LANES {
+                let single = (k != 2)
+                    .then(|| DefaultShader.fragment(frag_coords[k], &varyings.lane(k), &uniforms))
+                    .flatten();
+                assert_eq!(block[k], single);
+            }
+        }
+    }
+}
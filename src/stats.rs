@@ -0,0 +1,24 @@
+// counters of a draw, to see how much work the hierarchical z test saves
+// a binned triangle is counted once for every tile it overlaps
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct RasterStats {
+    pub triangles: u64,
+    // rejected with a single compare against the max depth of the whole tile
+    pub tile_culled: u64,
+    // rejected by the max depth of the 8x8 cells under the triangle
+    pub cell_culled: u64,
+}
+
+impl RasterStats {
+    pub fn culled(&self) -> u64 {
+        self.tile_culled + self.cell_culled
+    }
+}
+
+impl std::ops::AddAssign for RasterStats {
+    fn add_assign(&mut self, other: Self) {
+        self.triangles += other.triangles;
+        self.tile_culled += other.tile_culled;
+        self.cell_culled += other.cell_culled;
+    }
+}
@@ -0,0 +1,253 @@
+use crate::CompareFunction;
+
+// what happens to the stencil value of a sample, `Increment` and `Decrement` saturate
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Increment,
+    Decrement,
+    IncrementWrap,
+    DecrementWrap,
+    Invert,
+}
+
+impl StencilOp {
+    pub fn apply(self, value: u8, reference: u8) -> u8 {
+        match self {
+            StencilOp::Keep => value,
+            StencilOp::Zero => 0,
+            StencilOp::Replace => reference,
+            StencilOp::Increment => value.saturating_add(1),
+            StencilOp::Decrement => value.saturating_sub(1),
+            StencilOp::IncrementWrap => value.wrapping_add(1),
+            StencilOp::DecrementWrap => value.wrapping_sub(1),
+            StencilOp::Invert => !value,
+        }
+    }
+}
+
+// the test and the ops for one facing of triangles
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StencilFace {
+    // passes if `reference compare stored`, both masked by the read mask
+    pub compare: CompareFunction,
+    // stencil test failed
+    pub fail_op: StencilOp,
+    // stencil test passed, depth test failed
+    pub depth_fail_op: StencilOp,
+    // both passed and the fragment shader did not discard
+    pub pass_op: StencilOp,
+}
+
+impl Default for StencilFace {
+    fn default() -> Self {
+        Self {
+            compare: CompareFunction::Always,
+            fail_op: StencilOp::Keep,
+            depth_fail_op: StencilOp::Keep,
+            pass_op: StencilOp::Keep,
+        }
+    }
+}
+
+impl StencilFace {
+    // same test for every face, writing `pass_op` where it passes
+    pub fn new(compare: CompareFunction, pass_op: StencilOp) -> Self {
+        Self {
+            compare,
+            pass_op,
+            ..Default::default()
+        }
+    }
+
+    // fragments that fail a test leave the stencil alone, so a triangle known to fail the depth
+    // test everywhere can be skipped
+    pub fn only_writes_on_pass(&self) -> bool {
+        self.fail_op == StencilOp::Keep && self.depth_fail_op == StencilOp::Keep
+    }
+}
+
+// the stencil test of a draw, separate for front and back faces so both can be counted in one pass
+// like the faces of shadow volumes
+// it runs before the depth test and before shading, so `fail_op` and `depth_fail_op` also apply to
+// fragments the shader would discard, and only touches framebuffers that have a stencil attachment
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StencilState {
+    pub front: StencilFace,
+    pub back: StencilFace,
+    pub reference: u8,
+    pub read_mask: u8,
+    pub write_mask: u8,
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        Self::new(StencilFace::default())
+    }
+}
+
+impl StencilState {
+    pub fn new(face: StencilFace) -> Self {
+        Self {
+            front: face,
+            back: face,
+            reference: 0,
+            read_mask: 0xff,
+            write_mask: 0xff,
+        }
+    }
+
+    pub fn face(&self, front_facing: bool) -> &StencilFace {
+        if front_facing {
+            &self.front
+        } else {
+            &self.back
+        }
+    }
+
+    pub fn test(&self, face: &StencilFace, stored: u8) -> bool {
+        face.compare
+            .test(self.reference & self.read_mask, stored & self.read_mask)
+    }
+
+    // stencil test of one sample together with the result of its depth test, applies the fail ops
+    // returns true if the sample goes on to be shaded, `pass_op` is up to the caller then
+    pub fn test_and_update(&self, face: &StencilFace, depth_passed: bool, stored: &mut u8) -> bool {
+        if !self.test(face, *stored) {
+            self.apply(face.fail_op, stored);
+            false
+        } else if !depth_passed {
+            self.apply(face.depth_fail_op, stored);
+            false
+        } else {
+            true
+        }
+    }
+
+    // only the bits in the write mask change
+    pub fn apply(&self, op: StencilOp, stored: &mut u8) {
+        let value = op.apply(*stored, self.reference);
+        *stored = (*stored & !self.write_mask) | (value & self.write_mask);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn ops_respect_the_masks() {
+        let state = StencilState {
+            reference: 0b1010_0101,
+            read_mask: 0x0f,
+            write_mask: 0xf0,
+            ..Default::default()
+        };
+        let mut stored = 0b0000_0101;
+        let face = StencilFace::new(CompareFunction::Equal, StencilOp::Replace);
+        // only the low bits are compared, only the high bits are written
+        assert!(state.test(&face, stored | 0xf0));
+        state.apply(StencilOp::Replace, &mut stored);
+        assert_eq!(stored, 0b1010_0101);
+        state.apply(StencilOp::Invert, &mut stored);
+        assert_eq!(stored, 0b0101_0101);
+        state.apply(StencilOp::Zero, &mut stored);
+        assert_eq!(stored, 0b0000_0101);
+
+        assert_eq!(StencilOp::Increment.apply(255, 0), 255);
+        assert_eq!(StencilOp::IncrementWrap.apply(255, 0), 0);
+        assert_eq!(StencilOp::Decrement.apply(0, 0), 0);
+        assert_eq!(StencilOp::DecrementWrap.apply(0, 0), 255);
+    }
+
+    fn stenciled(stencil: StencilState) -> PipelineState {
+        PipelineState {
+            cull_mode: CullMode::None,
+            stencil: Some(stencil),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mask_limits_drawing() {
+        for samples in [1, 4] {
+            let mut framebuffer =
+                Framebuffer::with_samples(64, 16, SamplePattern::standard(samples)).with_stencil();
+            // the left half writes 1 into the stencil
+            let mark = StencilState {
+                reference: 1,
+                ..StencilState::new(StencilFace::new(
+                    CompareFunction::Always,
+                    StencilOp::Replace,
+                ))
+            };
+            draw(
+                &quad(-1.0, 0.0, 0.5),
+                WHITE,
+                &stenciled(mark),
+                &mut framebuffer,
+            );
+            framebuffer.clear_color(0);
+            framebuffer.clear_depth(f32::INFINITY);
+
+            // a full screen quad only shows up where it is 1
+            let masked = StencilState {
+                reference: 1,
+                ..StencilState::new(StencilFace::new(CompareFunction::Equal, StencilOp::Keep))
+            };
+            draw(
+                &quad(-1.0, 1.0, 0.5),
+                WHITE,
+                &stenciled(masked),
+                &mut framebuffer,
+            );
+            for y in 0..16 {
+                for x in 0..64 {
+                    let id = framebuffer.index(x, y).unwrap();
+                    assert_eq!(framebuffer.color()[id] != 0, x < 32, "{} {}", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn depth_fail_counts_hidden_layers() {
+        let mut framebuffer = Framebuffer::new(64, 16).with_stencil();
+        draw(
+            &quad(-1.0, 1.0, 0.2),
+            WHITE,
+            &stenciled(StencilState::default()),
+            &mut framebuffer,
+        );
+
+        // two layers behind the first one on the right, one in front on the left
+        let count = StencilState::new(StencilFace {
+            depth_fail_op: StencilOp::Increment,
+            ..Default::default()
+        });
+        draw(
+            &quad(0.0, 1.0, 0.6),
+            WHITE,
+            &stenciled(count),
+            &mut framebuffer,
+        );
+        draw(
+            &quad(0.0, 1.0, 0.7),
+            WHITE,
+            &stenciled(count),
+            &mut framebuffer,
+        );
+        draw(
+            &quad(-1.0, 0.0, 0.1),
+            WHITE,
+            &stenciled(count),
+            &mut framebuffer,
+        );
+        let stencil = framebuffer.stencil().unwrap();
+        assert_eq!(stencil[coords_to_index(10, 8, 64)], 0);
+        assert_eq!(stencil[coords_to_index(50, 8, 64)], 2);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    raster_mesh, ClipVertex, FragmentShader, Framebuffer, Mesh, PipelineState, RasterStats,
+    RenderSettings, Vertex, VertexShader,
+};
+use glam::{UVec3, Vec2, Vec3, Vec4};
+
+// meshes, a shader and a draw call shared by the tests of the pipeline stages
+
+// a full height quad from `left` to `right` in clip space at depth `z`,
+// split into two triangles larger than a hi-z cell
+pub fn quad(left: f32, right: f32, z: f32) -> Mesh {
+    let vertex =
+        |x: f32, y: f32| Vertex::new(Vec4::new(x, y, z, 1.0), Vec3::Z, Vec3::ONE, Vec2::ZERO);
+    Mesh {
+        vertices: vec![
+            vertex(left, -1.0),
+            vertex(right, -1.0),
+            vertex(left, 1.0),
+            vertex(right, 1.0),
+        ],
+        triangle_indices: vec![UVec3::new(0, 1, 2), UVec3::new(1, 3, 2)],
+        ..Default::default()
+    }
+}
+
+pub const WHITE: u32 = 0xffffff;
+
+// passes the positions through and fills with the color in the uniforms
+pub struct FlatShader;
+
+impl VertexShader<u32> for FlatShader {
+    type Varyings = ();
+
+    fn vertex(&self, vertex: &Vertex, _: &u32) -> ClipVertex<()> {
+        ClipVertex::new(vertex.pos, ())
+    }
+}
+
+impl FragmentShader<u32, ()> for FlatShader {
+    fn fragment(&self, _: Vec4, _: &(), color: &u32) -> Option<u32> {
+        Some(*color)
+    }
+}
+
+pub fn draw(
+    mesh: &Mesh,
+    color: u32,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) -> RasterStats {
+    raster_mesh(
+        mesh,
+        &FlatShader,
+        &FlatShader,
+        &color,
+        pipeline,
+        &RenderSettings::default(),
+        framebuffer,
+    )
+}
@@ -9,52 +9,79 @@ pub struct Texture {
     pub depth: usize,
 }
 
+// repeat addressing, integer division is slow enough to show up per fragment
+// and coordinates are nearly always in range already
+fn wrap(texel: usize, size: usize) -> usize {
+    if texel < size {
+        texel
+    } else {
+        texel % size
+    }
+}
+
 impl Texture {
     pub fn load(path: &Path) -> Self {
-        let decoded_image = stb_image::image::load(path);
-        if let stb_image::image::LoadResult::ImageU8(image) = decoded_image {
-            let data;
-            if image.depth == 4 {
-                data = (0..image.data.len() / 4)
-                    .map(|id| {
-                        to_argb8(
-                            image.data[id * 4 + 3],
-                            image.data[id * 4],
-                            image.data[id * 4 + 1],
-                            image.data[id * 4 + 2],
-                        )
-                    })
-                    .collect();
-            } else {
-                data = (0..image.data.len() / 3)
-                    .map(|id| {
-                        from_u8_rgb(
-                            image.data[id * 3],
-                            image.data[id * 3 + 1],
-                            image.data[id * 3 + 2],
-                        )
-                    })
-                    .collect();
-            }
-            Self {
-                width: image.width,
-                height: image.height,
-                data,
-                depth: image.depth,
+        Self::try_load(path).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // like `load`, for paths that come from the user, a file stb_image can't decode is an error
+    pub fn try_load(path: &Path) -> Result<Self, String> {
+        match stb_image::image::load(path) {
+            stb_image::image::LoadResult::ImageU8(image) => Ok(Self::from_pixels(
+                image.width,
+                image.height,
+                image.depth,
+                &image.data,
+            )),
+            stb_image::image::LoadResult::ImageF32(_) => {
+                Err("Unsupported texture type".to_string())
             }
+            stb_image::image::LoadResult::Error(e) => Err(e),
+        }
+    }
+
+    // 8 bit rgb or rgba pixels, `depth` is the number of channels, rgb ones are opaque
+    pub fn from_pixels(width: usize, height: usize, depth: usize, pixels: &[u8]) -> Self {
+        let data = if depth == 4 {
+            pixels
+                .chunks_exact(4)
+                .map(|p| to_argb8(p[3], p[0], p[1], p[2]))
+                .collect()
         } else {
-            panic!("Unsupported texture type");
+            pixels
+                .chunks_exact(3)
+                .map(|p| from_u8_argb(255, p[0], p[1], p[2]))
+                .collect()
+        };
+        Self {
+            width,
+            height,
+            data,
+            depth,
         }
     }
 
     pub fn rgb_at_uv(&self, u: f32, v: f32) -> u32 {
         let (u, v) = (u * self.width as f32, v * self.height as f32);
-        let (u, v) = (u as usize % self.width, v as usize % self.height);
-        let id = u as usize + v as usize * self.width;
+        let (u, v) = (wrap(u as usize, self.width), wrap(v as usize, self.height));
+        let id = u + v * self.width;
         if id < self.data.len() {
             self.data[id]
         } else {
-            from_u8_rgb(255, 0, 255)
+            from_u8_argb(255, 255, 0, 255)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::path::Path;
+
+    #[test]
+    fn try_load_rejects_files_that_are_no_image() {
+        assert!(Texture::try_load(Path::new("Cargo.toml")).is_err());
+        let texture = Texture::try_load(Path::new("assets/textures/bee_icon_256.png")).unwrap();
+        assert_eq!((texture.width, texture.height), (256, 256));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    raster_screen_triangle, FragmentShader, Framebuffer, FramebufferBand, HiZ, PipelineState,
+    RasterStats, ScreenTriangle, Varying,
+};
+use std::sync::Mutex;
+
+// size of a square screen tile in pixels
+// a row of tiles is one band of the buffer, bands are handed out to the worker threads
+pub const TILE_SIZE: usize = 64;
+
+// pixel rectangle, all bounds are inclusive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tile {
+    pub left: usize,
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+}
+
+impl Tile {
+    pub fn new(left: usize, top: usize, right: usize, bottom: usize) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn intersect(&self, other: &Tile) -> Option<Tile> {
+        let rect = Tile::new(
+            self.left.max(other.left),
+            self.top.max(other.top),
+            self.right.min(other.right),
+            self.bottom.min(other.bottom),
+        );
+        (rect.left <= rect.right && rect.top <= rect.bottom).then_some(rect)
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.left..=self.right).contains(&x) && (self.top..=self.bottom).contains(&y)
+    }
+}
+
+// per tile lists of triangle indices, kept in submission order
+pub struct TileBins {
+    pub width: usize,
+    pub height: usize,
+    pub tiles_x: usize,
+    pub tiles_y: usize,
+    pub bins: Vec<Vec<usize>>,
+}
+
+impl TileBins {
+    pub fn new(width: usize, height: usize) -> Self {
+        let tiles_x = width.div_ceil(TILE_SIZE);
+        let tiles_y = height.div_ceil(TILE_SIZE);
+        Self {
+            width,
+            height,
+            tiles_x,
+            tiles_y,
+            bins: vec![Vec::new(); tiles_x * tiles_y],
+        }
+    }
+
+    pub fn tile(&self, tile_x: usize, tile_y: usize) -> Tile {
+        let left = tile_x * TILE_SIZE;
+        let top = tile_y * TILE_SIZE;
+        Tile::new(
+            left,
+            top,
+            (left + TILE_SIZE).min(self.width) - 1,
+            (top + TILE_SIZE).min(self.height) - 1,
+        )
+    }
+
+    pub fn clear(&mut self) {
+        self.bins.iter_mut().for_each(|bin| bin.clear());
+    }
+
+    // the bounding boxes are already clamped to the viewport, so they always land in valid tiles
+    pub fn bin<V>(&mut self, triangles: &[ScreenTriangle<V>]) {
+        for (id, triangle) in triangles.iter().enumerate() {
+            let bb = &triangle.bb;
+            let first_x = bb.left as usize / TILE_SIZE;
+            let last_x = bb.right as usize / TILE_SIZE;
+            let first_y = bb.top as usize / TILE_SIZE;
+            let last_y = bb.bottom as usize / TILE_SIZE;
+            for tile_y in first_y..=last_y {
+                for tile_x in first_x..=last_x {
+                    self.bins[tile_x + tile_y * self.tiles_x].push(id);
+                }
+            }
+        }
+    }
+}
+
+pub fn worker_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+// every band of tiles is rasterized by exactly one thread, so no pixel is shared between threads
+// and triangles are drawn per tile in the same order as they were submitted
+// each band keeps its own hierarchical z, triangles already hidden in a tile are skipped
+// the hierarchical z only summarizes single sampled depth and is not used for multisampled framebuffers
+pub fn raster_tiles<U, V, FS>(
+    bins: &TileBins,
+    triangles: &[ScreenTriangle<V>],
+    fragment_shader: &FS,
+    uniforms: &U,
+    pipeline: &PipelineState,
+    framebuffer: &mut Framebuffer,
+) -> RasterStats
+where
+    U: Sync,
+    V: Varying + Sync,
+    FS: FragmentShader<U, V> + Sync,
+{
+    let queue = Mutex::new(framebuffer.bands_mut(TILE_SIZE));
+
+    let raster_band = |mut band: FramebufferBand| {
+        let mut stats = RasterStats::default();
+        let tile_y = band.first_row / TILE_SIZE;
+        let mut hiz = HiZ::new(
+            band.width,
+            band.first_row,
+            band.rows(),
+            band.depth_range.depth_compare(pipeline.depth.compare),
+        );
+        for tile_x in 0..bins.tiles_x {
+            let tile = bins.tile(tile_x, tile_y);
+            for &id in &bins.bins[tile_x + tile_y * bins.tiles_x] {
+                let triangle = &triangles[id];
+                let Some(rect) = triangle.tile_rect(&tile) else {
+                    continue;
+                };
+                stats.triangles += 1;
+                if band.samples.count() > 1 {
+                    raster_screen_triangle(
+                        triangle,
+                        &tile,
+                        fragment_shader,
+                        uniforms,
+                        pipeline,
+                        &mut band,
+                    );
+                    continue;
+                }
+                // a hidden triangle still has to run the stencil ops of its failing fragments
+                let stencil = band.stencil_state(pipeline, triangle.front_facing);
+                let cullable =
+                    hiz.is_usable() && stencil.is_none_or(|(_, face)| face.only_writes_on_pass());
+                if cullable && hiz.tile_occludes(&tile, triangle) {
+                    stats.tile_culled += 1;
+                    continue;
+                }
+                if cullable && hiz.rect_occludes(&rect, triangle, band.depth) {
+                    stats.cell_culled += 1;
+                    continue;
+                }
+                let complete = raster_screen_triangle(
+                    triangle,
+                    &tile,
+                    fragment_shader,
+                    uniforms,
+                    pipeline,
+                    &mut band,
+                );
+                if !hiz.is_usable() {
+                    continue;
+                }
+                // fragments failing the stencil test keep their old depth, like discarded ones
+                hiz.update(
+                    &rect,
+                    triangle,
+                    complete
+                        && stencil.is_none()
+                        && pipeline.depth.write
+                        && !band.collects_fragments(pipeline),
+                );
+            }
+        }
+        stats
+    };
+
+    let workers = worker_count().min(bins.tiles_y);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut stats = RasterStats::default();
+                    loop {
+                        let band = queue.lock().unwrap().next();
+                        match band {
+                            Some(band) => stats += raster_band(band),
+                            None => break stats,
+                        }
+                    }
+                })
+            })
+            .collect();
+        let mut stats = RasterStats::default();
+        for handle in handles {
+            stats += handle.join().unwrap();
+        }
+        stats
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::path::Path;
+
+    #[test]
+    fn tiled_matches_serial() {
+        let (width, height) = (300, 200);
+        let texture = Texture::load(Path::new("assets/textures/bee_icon_256.png"));
+        let mesh = load_gltf(Path::new("assets/gltf_models/teapot.gltf"));
+        let camera = Camera {
+            aspect_ratio: width as f32 / height as f32,
+            transform: Transform::from_translation(glam::vec3(0.0, 0.0, 4.0)),
+            ..Default::default()
+        };
+        let model = Transform::from_rotation(glam::Quat::from_rotation_y(0.7)).local();
+        let mvp = camera.projection() * camera.view() * model;
+        let uniforms = DefaultUniforms::new(mvp, model, Some(&texture));
+        let settings = RenderSettings::default();
+
+        let background = from_u8_rgb(100, 100, 200);
+        let mut serial = Framebuffer::new(width, height);
+        serial.clear(background);
+        for triangle_indices in &mesh.triangle_indices {
+            let vertices = mesh.get_vertices_from_triangle_indices(*triangle_indices);
+            raster_triangle(
+                &vertices,
+                &DefaultShader,
+                &DefaultShader,
+                &uniforms,
+                &PipelineState::default(),
+                &settings,
+                &mut serial,
+            );
+        }
+
+        let mut tiled = Framebuffer::new(width, height);
+        tiled.clear(background);
+        raster_mesh(
+            &mesh,
+            &DefaultShader,
+            &DefaultShader,
+            &uniforms,
+            &PipelineState::default(),
+            &settings,
+            &mut tiled,
+        );
+
+        assert!(serial.color().iter().any(|&c| c != background));
+        assert_eq!(serial.color(), tiled.color());
+        assert_eq!(serial.depth(), tiled.depth());
+    }
+}
@@ -1,4 +1,4 @@
-use glam::{Vec2, Vec3};
+use glam::{Vec2, Vec3, Vec4};
 
 pub fn from_u8_rgb(r: u8, g: u8, b: u8) -> u32 {
     u32::from_be_bytes([0, r, g, b])
@@ -16,6 +16,22 @@ pub fn to_argb8(a: u8, r: u8, g: u8, b: u8) -> u32 {
     argb
 }
 
+// ARGB as (r, g, b, a) between 0 and 1
+pub fn argb_to_vec4(color: u32) -> Vec4 {
+    Vec4::new(
+        ((color >> 16) & 0xff) as f32,
+        ((color >> 8) & 0xff) as f32,
+        (color & 0xff) as f32,
+        (color >> 24) as f32,
+    ) / 255.0
+}
+
+// the inverse of `argb_to_vec4`, channels outside of 0..1 are clamped
+pub fn vec4_to_argb(color: Vec4) -> u32 {
+    let c = (color.clamp(Vec4::ZERO, Vec4::ONE) * 255.0).round();
+    from_u8_argb(c.w as u8, c.x as u8, c.y as u8, c.z as u8)
+}
+
 pub fn index_to_coords(p: usize, width: usize) -> (usize, usize) {
     (p % width, p / width)
 }
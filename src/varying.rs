@@ -0,0 +1,249 @@
+use glam::{Vec2, Vec3, Vec4};
+
+// anything that can be interpolated across a triangle
+// the clipper blends varyings with `lerp`, the rasterizer uses `add` and `scale`
+// to do the perspective correct barycentric interpolation
+pub trait Varying: Copy {
+    fn add(self, other: Self) -> Self;
+    fn scale(self, factor: f32) -> Self;
+
+    fn lerp(self, end: Self, alpha: f32) -> Self {
+        self.scale(1.0 - alpha).add(end.scale(alpha))
+    }
+}
+
+impl Varying for f32 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        self * factor
+    }
+
+    fn lerp(self, end: Self, alpha: f32) -> Self {
+        self + (end - self) * alpha
+    }
+}
+
+macro_rules! impl_varying_vec {
+    ($($t:ty),*) => {
+        $(
+            impl Varying for $t {
+                fn add(self, other: Self) -> Self {
+                    self + other
+                }
+
+                fn scale(self, factor: f32) -> Self {
+                    self * factor
+                }
+
+                fn lerp(self, end: Self, alpha: f32) -> Self {
+                    self + (end - self) * alpha
+                }
+            }
+        )*
+    };
+}
+
+impl_varying_vec!(Vec2, Vec3, Vec4);
+
+// no varyings at all, for shaders that only output a position
+impl Varying for () {
+    fn add(self, _: Self) -> Self {}
+
+    fn scale(self, _: f32) -> Self {}
+}
+
+macro_rules! impl_varying_tuple {
+    ($($name:ident : $id:tt),*) => {
+        impl<$($name: Varying),*> Varying for ($($name,)*) {
+            fn add(self, other: Self) -> Self {
+                ($(self.$id.add(other.$id),)*)
+            }
+
+            fn scale(self, factor: f32) -> Self {
+                ($(self.$id.scale(factor),)*)
+            }
+
+            fn lerp(self, end: Self, alpha: f32) -> Self {
+                ($(self.$id.lerp(end.$id, alpha),)*)
+            }
+        }
+    };
+}
+
+impl_varying_tuple!(A: 0);
+impl_varying_tuple!(A: 0, B: 1);
+impl_varying_tuple!(A: 0, B: 1, C: 2);
+impl_varying_tuple!(A: 0, B: 1, C: 2, D: 3);
+impl_varying_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_varying_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+
+impl<T: Varying, const N: usize> Varying for [T; N] {
+    fn add(self, other: Self) -> Self {
+        std::array::from_fn(|i| self[i].add(other[i]))
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        self.map(|v| v.scale(factor))
+    }
+
+    fn lerp(self, end: Self, alpha: f32) -> Self {
+        std::array::from_fn(|i| self[i].lerp(end[i], alpha))
+    }
+}
+
+// implements `Varying` for a struct field by field, every field has to be a `Varying` itself
+// impl_varying!(MyVaryings { normal, tangent, uv });
+#[macro_export]
+macro_rules! impl_varying {
+    ($t:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::Varying for $t {
+            fn add(self, other: Self) -> Self {
+                Self {
+                    $($field: $crate::Varying::add(self.$field, other.$field),)*
+                }
+            }
+
+            fn scale(self, factor: f32) -> Self {
+                Self {
+                    $($field: $crate::Varying::scale(self.$field, factor),)*
+                }
+            }
+
+            fn lerp(self, end: Self, alpha: f32) -> Self {
+                Self {
+                    $($field: $crate::Varying::lerp(self.$field, end.$field, alpha),)*
+                }
+            }
+        }
+    };
+}
+
+// position in clip space plus whatever the vertex shader wants interpolated
+#[derive(Debug, Copy, Clone)]
+pub struct ClipVertex<V> {
+    pub pos: Vec4,
+    pub varyings: V,
+}
+
+impl<V: Varying> ClipVertex<V> {
+    pub fn new(pos: Vec4, varyings: V) -> Self {
+        Self { pos, varyings }
+    }
+
+    pub fn lerp(&self, end: &Self, alpha: f32) -> Self {
+        Self {
+            pos: self.pos.lerp(end.pos, alpha),
+            varyings: self.varyings.lerp(end.varyings, alpha),
+        }
+    }
+}
+
+// the varyings of a block of LANES neighbouring pixels, as the varyings of the three vertices
+// (already divided by their w) and the perspective correct weight of each vertex in every lane
+// `lane` interpolates all the varyings of one pixel, `lanes` one float of every pixel at once,
+// straight into a Vec4 without going through the per pixel structs
+#[derive(Debug, Copy, Clone)]
+pub struct VaryingBlock<'a, V> {
+    pub pv: &'a [V; 3],
+    pub weights: [Vec4; 3],
+}
+
+impl<'a, V: Varying> VaryingBlock<'a, V> {
+    pub fn new(pv: &'a [V; 3], weights: [Vec4; 3]) -> Self {
+        Self { pv, weights }
+    }
+
+    pub fn lane(&self, k: usize) -> V {
+        let [w0, w1, w2] = self.weights;
+        self.pv[0]
+            .scale(w0[k])
+            .add(self.pv[1].scale(w1[k]))
+            .add(self.pv[2].scale(w2[k]))
+    }
+
+    // `component` has to pick a float out of the varyings, like `|v| v.uv.x`,
+    // anything that isn't linear in the varyings is not interpolated correctly
+    // the math is the same as in `lane`, so both give the exact same value
+    pub fn lanes(&self, component: impl Fn(&V) -> f32) -> Vec4 {
+        let [w0, w1, w2] = self.weights;
+        w0 * component(&self.pv[0]) + w1 * component(&self.pv[1]) + w2 * component(&self.pv[2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use glam::{Vec2, Vec4};
+
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct Custom {
+        tangent: Vec4,
+        uv1: Vec2,
+        weights: [f32; 4],
+    }
+
+    crate::impl_varying!(Custom {
+        tangent,
+        uv1,
+        weights
+    });
+
+    #[test]
+    fn custom_varyings_survive_clipping() {
+        let custom = |s: f32| Custom {
+            tangent: Vec4::splat(s),
+            uv1: Vec2::splat(s * 2.0),
+            weights: [s, 1.0 - s, 0.0, 0.0],
+        };
+        // v0 is behind the near plane, halfway between v0 and the other vertices is z = 0
+        let triangle = Triangle::new(
+            ClipVertex::new(Vec4::new(0.0, 0.0, -1.0, 1.0), (custom(0.0), 1.0)),
+            ClipVertex::new(Vec4::new(0.5, 0.0, 1.0, 1.0), (custom(1.0), 3.0)),
+            ClipVertex::new(Vec4::new(0.0, 0.5, 1.0, 1.0), (custom(1.0), 3.0)),
+        );
+        match clip_cull_triangle(&triangle, 1.0) {
+            ClipResult::Fan(polygon) => {
+                assert_eq!(polygon.len(), 4);
+                for v in polygon {
+                    if v.pos.z == 0.0 {
+                        assert_eq!(v.varyings, (custom(0.5), 2.0));
+                    } else {
+                        assert_eq!(v.varyings, (custom(1.0), 3.0));
+                    }
+                }
+            }
+            _ => panic!("expected the triangle to be clipped into a quad"),
+        }
+    }
+
+    #[test]
+    fn block_lanes_match_lane() {
+        let custom = |s: f32| Custom {
+            tangent: Vec4::new(s, -s, 0.3 * s, 1.0),
+            uv1: Vec2::new(s * 2.0, 0.7),
+            weights: [s, 1.0 - s, 0.1, 0.0],
+        };
+        let pv = [custom(0.1), custom(0.6), custom(0.9)];
+        let block = VaryingBlock::new(
+            &pv,
+            [
+                Vec4::new(0.1, 0.25, 0.5, 1.5),
+                Vec4::new(0.3, 0.7, 0.125, -0.2),
+                Vec4::new(0.6, 0.05, 0.375, -0.3),
+            ],
+        );
+        let tangent_z = block.lanes(|v| v.tangent.z);
+        let uv1_x = block.lanes(|v| v.uv1.x);
+        let weight = block.lanes(|v| v.weights[1]);
+        for k in 0..LANES {
+            let lane = block.lane(k);
+            // bit for bit, not just close
+            assert_eq!(tangent_z[k], lane.tangent.z);
+            assert_eq!(uv1_x[k], lane.uv1.x);
+            assert_eq!(weight[k], lane.weights[1]);
+        }
+    }
+}
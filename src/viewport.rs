@@ -0,0 +1,167 @@
+use crate::{map_to_range, Tile};
+use glam::Vec2;
+
+// the rectangle of the framebuffer normalized device coordinates are mapped to, in pixels
+// -1..1 in x goes from `offset.x` to `offset.x + size.x`, the same for y
+// several viewports on one framebuffer give split screen, picture in picture or a minimap
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub offset: Vec2,
+    pub size: Vec2,
+}
+
+impl Viewport {
+    pub fn new(offset: Vec2, size: Vec2) -> Self {
+        Self { offset, size }
+    }
+
+    // covering a whole framebuffer of `size`, what drawing uses when the pipeline has no viewport
+    pub fn full(size: Vec2) -> Self {
+        Self::new(Vec2::ZERO, size)
+    }
+
+    pub fn to_window(&self, ndc: Vec2) -> Vec2 {
+        let end = self.offset + self.size;
+        Vec2::new(
+            map_to_range(ndc.x, -1.0, 1.0, self.offset.x, end.x),
+            map_to_range(ndc.y, -1.0, 1.0, self.offset.y, end.y),
+        )
+    }
+
+    // the pixels with their center inside the viewport, within a framebuffer of `framebuffer_size`
+    // clipping only keeps triangles within the guard band, so they are cut to this as well
+    pub fn pixel_rect(&self, framebuffer_size: Vec2) -> Option<Tile> {
+        let first = (self.offset - 0.5).ceil().max(Vec2::ZERO);
+        let last = ((self.offset + self.size - 0.5).ceil() - 1.0).min(framebuffer_size - 1.0);
+        (first.cmple(last).all()).then(|| {
+            Tile::new(
+                first.x as usize,
+                first.y as usize,
+                last.x as usize,
+                last.y as usize,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use glam::{UVec3, Vec2, Vec3, Vec4};
+
+    #[test]
+    fn pixel_rect() {
+        let size = Vec2::new(64.0, 32.0);
+        assert_eq!(
+            Viewport::full(size).pixel_rect(size),
+            Some(Tile::new(0, 0, 63, 31))
+        );
+        let viewport = Viewport::new(Vec2::new(48.0, -8.0), Vec2::new(32.0, 16.0));
+        assert_eq!(viewport.pixel_rect(size), Some(Tile::new(48, 0, 63, 7)));
+        assert_eq!(viewport.to_window(Vec2::ZERO), Vec2::new(64.0, 0.0));
+        let outside = Viewport::new(Vec2::new(64.0, 0.0), Vec2::new(8.0, 8.0));
+        assert_eq!(outside.pixel_rect(size), None);
+    }
+
+    fn lit(framebuffer: &Framebuffer, rect: Tile) -> bool {
+        (0..framebuffer.height()).all(|y| {
+            (0..framebuffer.width()).all(|x| {
+                let inside =
+                    (rect.left..=rect.right).contains(&x) && (rect.top..=rect.bottom).contains(&y);
+                (framebuffer.color_at(x, y) != Some(0)) == inside
+            })
+        })
+    }
+
+    #[test]
+    fn viewports_and_scissor() {
+        for samples in [1, 4] {
+            // the right half, a full screen quad fills just that
+            let mut framebuffer =
+                Framebuffer::with_samples(128, 96, SamplePattern::standard(samples));
+            framebuffer.clear(0);
+            let pipeline = PipelineState {
+                cull_mode: CullMode::None,
+                viewport: Some(Viewport::new(Vec2::new(64.0, 0.0), Vec2::new(64.0, 96.0))),
+                ..Default::default()
+            };
+            let mesh = quad(-1.0, 1.0, 0.5);
+            draw(&mesh, WHITE, &pipeline, &mut framebuffer);
+            assert!(lit(&framebuffer, Tile::new(64, 0, 127, 95)));
+
+            // a triangle reaching far out of the view only gets as far as the guard band clips it,
+            // it is still kept inside the viewport
+            framebuffer.clear(0);
+            let big = Mesh {
+                vertices: vec![
+                    Vertex::new(
+                        Vec4::new(-3.0, -3.0, 0.5, 1.0),
+                        Vec3::Z,
+                        Vec3::ONE,
+                        Vec2::ZERO,
+                    ),
+                    Vertex::new(
+                        Vec4::new(5.0, -3.0, 0.5, 1.0),
+                        Vec3::Z,
+                        Vec3::ONE,
+                        Vec2::ZERO,
+                    ),
+                    Vertex::new(
+                        Vec4::new(-3.0, 5.0, 0.5, 1.0),
+                        Vec3::Z,
+                        Vec3::ONE,
+                        Vec2::ZERO,
+                    ),
+                ],
+                triangle_indices: vec![UVec3::new(0, 1, 2)],
+                ..Default::default()
+            };
+            draw(&big, WHITE, &pipeline, &mut framebuffer);
+            assert!(lit(&framebuffer, Tile::new(64, 0, 127, 95)));
+
+            // the scissor cuts it down further, without moving anything
+            framebuffer.clear(0);
+            let pipeline = PipelineState {
+                scissor: Some(Tile::new(70, 10, 80, 20)),
+                ..pipeline
+            };
+            draw(&mesh, WHITE, &pipeline, &mut framebuffer);
+            assert!(lit(&framebuffer, Tile::new(70, 10, 80, 20)));
+
+            // lines stay inside too
+            framebuffer.clear(0);
+            raster_clip_line(
+                Vec4::new(-1.0, 0.0, 0.5, 1.0),
+                Vec4::new(1.0, 0.0, 0.5, 1.0),
+                0xff,
+                &PipelineState {
+                    scissor: None,
+                    ..pipeline
+                },
+                &mut framebuffer,
+            );
+            assert!(lit(&framebuffer, Tile::new(64, 48, 127, 48)));
+        }
+    }
+
+    #[test]
+    fn split_screen() {
+        // two views of the same quad side by side, each with its own color
+        let mut framebuffer = Framebuffer::new(64, 32);
+        framebuffer.clear(0);
+        for (i, color) in [0xff0000, 0x00ff00].into_iter().enumerate() {
+            let pipeline = PipelineState {
+                viewport: Some(Viewport::new(
+                    Vec2::new(32.0 * i as f32, 0.0),
+                    Vec2::new(32.0, 32.0),
+                )),
+                cull_mode: CullMode::None,
+                ..Default::default()
+            };
+            draw(&quad(-1.0, 1.0, 0.5), color, &pipeline, &mut framebuffer);
+        }
+        assert_eq!(framebuffer.color_at(31, 5), Some(0xff0000));
+        assert_eq!(framebuffer.color_at(32, 5), Some(0x00ff00));
+    }
+}
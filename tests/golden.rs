@@ -0,0 +1,249 @@
+// renders reference scenes headlessly and compares them against the images in tests/golden
+// a channel may be off by TOLERANCE before a pixel counts as different, any different pixel fails
+// on failure the actual frame and a diff image are written to target/tmp/golden
+// run with UPDATE_GOLDEN=1 to accept the current output as the new reference
+use glam::{Mat4, UVec3, Vec2, Vec3, Vec4};
+use rusterizer::*;
+use std::path::{Path, PathBuf};
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 120;
+const TOLERANCE: u8 = 2;
+const BACKGROUND: u32 = 0x646464;
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+fn load_rgb(path: &Path) -> Option<(usize, usize, Vec<u8>)> {
+    let decoder = png::Decoder::new(std::fs::File::open(path).ok()?);
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgb, "{}", path.display());
+    data.truncate(info.buffer_size());
+    Some((info.width as usize, info.height as usize, data))
+}
+
+// differing pixels in red over a faded copy of the reference
+fn diff_image(expected: &[u8], actual: &[u8]) -> (Framebuffer, usize) {
+    let mut diff = Framebuffer::new(WIDTH, HEIGHT);
+    let mut differing = 0;
+    for (id, (e, a)) in expected
+        .chunks_exact(3)
+        .zip(actual.chunks_exact(3))
+        .enumerate()
+    {
+        let (x, y) = index_to_coords(id, WIDTH);
+        if e.iter().zip(a).any(|(e, a)| e.abs_diff(*a) > TOLERANCE) {
+            differing += 1;
+            diff.set_color(x, y, from_u8_rgb(255, 0, 0));
+        } else {
+            let gray = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 12) as u8;
+            diff.set_color(x, y, from_u8_rgb(gray, gray, gray));
+        }
+    }
+    (diff, differing)
+}
+
+fn check(name: &str, framebuffer: &Framebuffer) {
+    let golden = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        save_color(framebuffer, &golden).unwrap();
+        return;
+    }
+    let Some((width, height, expected)) = load_rgb(&golden) else {
+        panic!(
+            "missing {}, run with UPDATE_GOLDEN=1 to create it",
+            golden.display()
+        );
+    };
+    assert_eq!((width, height), (WIDTH, HEIGHT), "{}", golden.display());
+
+    let actual = color_to_rgb(framebuffer);
+    let (diff, differing) = diff_image(&expected, &actual);
+    if differing > 0 {
+        let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        std::fs::create_dir_all(&output).unwrap();
+        let actual_path = output.join(format!("{}.actual.png", name));
+        let diff_path = output.join(format!("{}.diff.png", name));
+        save_color(framebuffer, &actual_path).unwrap();
+        save_color(&diff, &diff_path).unwrap();
+        panic!(
+            "{}: {} pixels differ from {}, see {} and {}",
+            name,
+            differing,
+            golden.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn camera(distance: f32) -> Camera {
+    Camera {
+        aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+        transform: Transform::from_translation(glam::vec3(0.0, 0.0, distance)),
+        frustum_near: 0.5,
+        frustum_far: 100.0,
+        ..Default::default()
+    }
+}
+
+fn render(
+    mesh: &Mesh,
+    uniforms: &DefaultUniforms,
+    pipeline: &PipelineState,
+    settings: &RenderSettings,
+) -> Framebuffer {
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    framebuffer.clear(BACKGROUND);
+    raster_mesh(
+        mesh,
+        &DefaultShader,
+        &DefaultShader,
+        uniforms,
+        pipeline,
+        settings,
+        &mut framebuffer,
+    );
+    framebuffer
+}
+
+fn vertex(x: f32, y: f32, z: f32, uv: Vec2) -> Vertex {
+    Vertex::new(Vec4::new(x, y, z, 1.0), Vec3::Z, Vec3::ONE, uv)
+}
+
+fn quad(center: Vec2, size: f32, z: f32) -> Mesh {
+    let h = size / 2.0;
+    Mesh {
+        vertices: vec![
+            vertex(center.x - h, center.y - h, z, Vec2::new(0.0, 0.0)),
+            vertex(center.x + h, center.y - h, z, Vec2::new(1.0, 0.0)),
+            vertex(center.x - h, center.y + h, z, Vec2::new(0.0, 1.0)),
+            vertex(center.x + h, center.y + h, z, Vec2::new(1.0, 1.0)),
+        ],
+        triangle_indices: vec![UVec3::new(0, 1, 2), UVec3::new(1, 3, 2)],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn teapot() {
+    let mesh = load_gltf(Path::new("assets/gltf_models/teapot.gltf"));
+    let model = Transform::from_rotation(glam::Quat::from_rotation_y(0.7)).local();
+    let camera = camera(9.0);
+    let uniforms = DefaultUniforms::new(camera.projection() * camera.view() * model, model, None);
+    let framebuffer = render(
+        &mesh,
+        &uniforms,
+        &PipelineState::default(),
+        &RenderSettings::default(),
+    );
+    check("teapot", &framebuffer);
+}
+
+#[test]
+fn helmet() {
+    let mesh = load_gltf(Path::new(
+        "assets/gltf_models/damaged_helmet/DamagedHelmet.gltf",
+    ));
+    let texture = Texture::load(Path::new(
+        "assets/gltf_models/damaged_helmet/Default_albedo.jpg",
+    ));
+    let model = Transform::from_rotation(glam::Quat::from_euler(
+        glam::EulerRot::XYZ,
+        std::f32::consts::FRAC_PI_2,
+        0.6,
+        0.0,
+    ))
+    .local();
+    let camera = camera(3.0);
+    let uniforms = DefaultUniforms::new(
+        camera.projection() * camera.view() * model,
+        model,
+        Some(&texture),
+    );
+    let framebuffer = render(
+        &mesh,
+        &uniforms,
+        &PipelineState::default(),
+        &RenderSettings::default(),
+    );
+    check("helmet", &framebuffer);
+}
+
+#[test]
+fn textured_quads() {
+    let texture = Texture::load(Path::new("assets/textures/bee_icon_256.png"));
+    // one tilted into the screen, so perspective correction shows up
+    let model = Transform::from_rotation(glam::Quat::from_rotation_x(-1.0)).local();
+    let camera = camera(2.5);
+    let uniforms = DefaultUniforms::new(
+        camera.projection() * camera.view() * model,
+        Mat4::IDENTITY,
+        Some(&texture),
+    );
+    let pipeline = PipelineState {
+        cull_mode: CullMode::None,
+        ..Default::default()
+    };
+    let mut framebuffer = render(
+        &quad(Vec2::new(0.0, 0.0), 2.0, 0.0),
+        &uniforms,
+        &pipeline,
+        &RenderSettings::default(),
+    );
+    // and one through the 2D path, in pixels, on the front layer
+    let camera_2d = Camera2D::new(framebuffer.size());
+    raster_mesh_2d(
+        &quad(Vec2::new(130.0, 30.0), 40.0, camera_2d.layers),
+        Some(&texture),
+        &camera_2d,
+        &PipelineState {
+            depth: DepthState {
+                compare: CompareFunction::LessEqual,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        &mut framebuffer,
+    );
+    check("textured_quads", &framebuffer);
+}
+
+#[test]
+fn clipped_triangles() {
+    // a floor reaching from behind the camera into the distance and out both sides,
+    // drawn with clip visualization so every triangle the clipper produced is visible
+    let floor = Mesh {
+        vertices: vec![
+            vertex(-20.0, -1.0, 2.0, Vec2::ZERO),
+            vertex(20.0, -1.0, 2.0, Vec2::ZERO),
+            vertex(-20.0, -1.0, -30.0, Vec2::ZERO),
+            vertex(20.0, -1.0, -30.0, Vec2::ZERO),
+        ],
+        triangle_indices: vec![UVec3::new(0, 1, 2), UVec3::new(1, 3, 2)],
+        ..Default::default()
+    };
+    let camera = camera(1.0);
+    let uniforms = DefaultUniforms::new(camera.projection() * camera.view(), Mat4::IDENTITY, None);
+    let pipeline = PipelineState {
+        cull_mode: CullMode::None,
+        ..Default::default()
+    };
+    for (name, guard_band) in [
+        ("clipped_triangles", 1.0),
+        ("clipped_triangles_guard_band", 2.0),
+    ] {
+        let settings = RenderSettings {
+            clip_visualization: true,
+            guard_band,
+            ..Default::default()
+        };
+        check(name, &render(&floor, &uniforms, &pipeline, &settings));
+    }
+}